@@ -30,6 +30,9 @@ pf.columns = [
     "cost_of_javascript",
     "cost_of_impl",
     "cost_of_cpp",
+    "track",
+    "boundary",
+    "diagnostics",
 ]
 
 #create_histograms(pf)
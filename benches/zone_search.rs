@@ -0,0 +1,176 @@
+use calculate_differences::{
+    symbols::Symbols,
+    zone_search::{get_contained, get_parents, get_partial_contained, Zones},
+    zones::Zone,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// Builds a capture that looks like a busy main thread.  Each frame is a call stack a few levels
+// deep with a handful of leaf calls, and with `other_track` an unrelated track records zones over
+// the same time.
+fn capture(frames: u64, other_track: bool) -> Zones {
+    let mut names = Symbols::default();
+    let (frame_name, update_name, leaf_name, inner_name, other_name) = (
+        names.intern("frame"),
+        names.intern("update"),
+        names.intern("leaf"),
+        names.intern("leaf.inner"),
+        names.intern("other"),
+    );
+    let mut zones = vec![];
+    let frame_len = 10_000;
+
+    for frame in 0..frames {
+        let start = frame * frame_len;
+        zones.push(Zone::new(frame_name, start, start + frame_len - 10, 0));
+        zones.push(Zone::new(update_name, start + 10, start + 9_000, 0));
+        for leaf in 0..8 {
+            let leaf_start = start + 100 + leaf * 1_000;
+            zones.push(Zone::new(leaf_name, leaf_start, leaf_start + 500, 0));
+            zones.push(Zone::new(inner_name, leaf_start + 100, leaf_start + 200, 0));
+        }
+        if other_track {
+            zones.push(Zone::new(other_name, start + 50, start + frame_len + 5_000, 1));
+        }
+    }
+
+    return Zones::new(zones, names);
+}
+
+// The zone search the interval tree replaced, as it was before it: walk out from the zone in both
+// directions, asking a filter whether to add each zone, skip it or stop.  Kept here so the
+// benchmarks compare against what queries used to cost.  The walk stops at the first zone of
+// another track that gets in its way, so with the other track it only finds part of what the tree
+// does, only the one track numbers compare the same answers.
+mod walk {
+    use calculate_differences::zones::Zone;
+
+    pub enum FilterResult {
+        Continue,
+        Add,
+        Break,
+    }
+
+    type ZoneFilter = Box<dyn Fn(&Zone, &Zone) -> FilterResult>;
+
+    fn partial_intersect_filter(zone: &Zone, z: &Zone) -> FilterResult {
+        if zone.partial_contains(z) {
+            return FilterResult::Add;
+        } else if zone.contains(z) || z.contains(zone) || zone.track_id != z.track_id {
+            return FilterResult::Continue;
+        }
+        return FilterResult::Break;
+    }
+
+    fn get_parent_filter(zone: &Zone, z: &Zone) -> FilterResult {
+        if z.contains(zone) {
+            return FilterResult::Add;
+        } else if zone.track_id != z.track_id || zone.start_time.abs_diff(z.start_time) < 100000 {
+            return FilterResult::Continue;
+        }
+        return FilterResult::Break;
+    }
+
+    fn contain_intersect_filter(zone: &Zone, z: &Zone) -> FilterResult {
+        if zone.contains(z) {
+            return FilterResult::Add;
+        }
+        return FilterResult::Break;
+    }
+
+    fn intersect_by_filter(zones: &[Zone], idx: usize, filter: ZoneFilter) -> Vec<usize> {
+        let mut out = vec![];
+        let zone = &zones[idx];
+
+        for z in zones[..idx].iter().rev() {
+            match filter(zone, z) {
+                FilterResult::Add => out.push(z.idx),
+                FilterResult::Break => break,
+                FilterResult::Continue => {}
+            }
+        }
+        for z in &zones[idx + 1..] {
+            match filter(zone, z) {
+                FilterResult::Add => out.push(z.idx),
+                FilterResult::Break => break,
+                FilterResult::Continue => {}
+            }
+        }
+
+        return out;
+    }
+
+    pub fn get_parents(zones: &[Zone], idx: usize) -> Vec<usize> {
+        return intersect_by_filter(zones, idx, Box::new(get_parent_filter));
+    }
+
+    pub fn get_contained(zones: &[Zone], idx: usize) -> Vec<usize> {
+        return intersect_by_filter(zones, idx, Box::new(contain_intersect_filter));
+    }
+
+    pub fn get_partial_contained(zones: &[Zone], idx: usize) -> Vec<usize> {
+        return intersect_by_filter(zones, idx, Box::new(partial_intersect_filter));
+    }
+}
+
+fn bench_zone_search(c: &mut Criterion) {
+    for (label, other_track) in [("one track", false), ("two tracks", true)] {
+        let zones = capture(50_000, other_track);
+        let frames = zones.by_name("frame").iter().copied().step_by(500).collect::<Vec<usize>>();
+        let leaves = zones.by_name("leaf.inner").iter().copied().step_by(500).collect::<Vec<usize>>();
+
+        let mut group = c.benchmark_group(format!("get_contained/{}", label));
+        group.bench_function("interval tree", |b| {
+            b.iter(|| {
+                for idx in &frames {
+                    black_box(get_contained(&zones, *idx));
+                }
+            })
+        });
+        group.bench_function("walk", |b| {
+            b.iter(|| {
+                for idx in &frames {
+                    black_box(walk::get_contained(&zones, *idx));
+                }
+            })
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("get_parents/{}", label));
+        group.bench_function("interval tree", |b| {
+            b.iter(|| {
+                for idx in &leaves {
+                    black_box(get_parents(&zones, *idx));
+                }
+            })
+        });
+        group.bench_function("walk", |b| {
+            b.iter(|| {
+                for idx in &leaves {
+                    black_box(walk::get_parents(&zones, *idx));
+                }
+            })
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("get_partial_contained/{}", label));
+        group.bench_function("interval tree", |b| {
+            b.iter(|| {
+                for idx in &frames {
+                    black_box(get_partial_contained(&zones, *idx));
+                }
+            })
+        });
+        group.bench_function("walk", |b| {
+            b.iter(|| {
+                for idx in &frames {
+                    black_box(walk::get_partial_contained(&zones, *idx));
+                }
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_zone_search);
+criterion_main!(benches);
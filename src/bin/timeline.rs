@@ -1,40 +1,197 @@
 use calculate_differences::{
+    cache::{cache_path, write_cache_file},
+    capture::Capture,
     error::TimelineError,
-    opts::TimelineOpts,
-    parse::{parse_tracks, parse_zones},
-    query::{query_config::QueryConfig, run_query},
-    zone_search::set_zone_idx,
+    opts::{Command, TimelineOpts},
+    output::ResultWriter,
+    parse::{open_rows, parse_source, parse_telemetry_file},
+    query::{
+        budget::check_budgets,
+        types::{DiffThreshold, QueryResult, Verdict},
+        query_config::QueryConfig,
+    },
 };
 use log::info;
-use structopt::StructOpt;
+use structopt::{
+    clap::{Error, ErrorKind},
+    StructOpt,
+};
+
+fn index(opts: &TimelineOpts, output: &Option<String>) -> Result<(), TimelineError> {
+    info!("parsing telemetry");
+    let telemetry = parse_source(&opts.file, opts.lenient)?;
+    report_skipped(opts, telemetry.skipped_rows);
+
+    let output = output.clone().unwrap_or_else(|| cache_path(&opts.file));
+    write_cache_file(&telemetry, &opts.file, &output)?;
+
+    return Ok(());
+}
+
+fn report_skipped(opts: &TimelineOpts, skipped_rows: usize) {
+    if skipped_rows > 0 {
+        eprintln!("skipped {} malformed rows in {}", skipped_rows, opts.file);
+    }
+}
+
+fn load(opts: &TimelineOpts) -> Result<Capture, TimelineError> {
+    info!("parsing telemetry: {}", opts.file);
+    let telemetry = parse_telemetry_file(opts)?;
+
+    info!("indexing zones");
+    let capture = Capture::new(telemetry, open_rows(opts)?);
+    report_skipped(opts, capture.skipped_rows());
+
+    return Ok(capture);
+}
+
+fn run(opts: TimelineOpts) -> Result<(), TimelineError> {
+    if let Some(Command::Index { output }) = &opts.cmd {
+        return index(&opts, output);
+    }
+
+    let query_file = match &opts.query_file {
+        Some(query_file) => query_file,
+        None => Error::with_description(
+            "--query-file is required unless running the index subcommand",
+            ErrorKind::MissingRequiredArgument,
+        )
+        .exit(),
+    };
+
+    info!("parsing query config");
+    let query_config: QueryConfig = query_file.parse()?;
+
+    let capture = load(&opts)?;
+    let baseline = match &opts.cmd {
+        Some(Command::Diff { baseline, alpha, min_change, resamples }) => Some((
+            load(&TimelineOpts {
+                file: baseline.clone(),
+                ..opts.clone()
+            })?,
+            DiffThreshold {
+                alpha: *alpha,
+                min_change: *min_change,
+                resamples: *resamples,
+            },
+        )),
+        _ => None,
+    };
+
+    info!("running queries: zones: {}", capture.zones().len());
+    let mut writer = ResultWriter::new(opts.output_format, std::io::stdout().lock());
+    let mut failed = 0;
+    let mut regressions = 0;
+    // only held on to when there are budgets to check them against.
+    let mut budgeted = vec![];
+    for (idx, query) in query_config.queries.iter().enumerate() {
+        info!("query: {:?}", query);
+        // a failing query is reported and skipped, the rest of the batch still runs.
+        let outcome = match &baseline {
+            Some((baseline, threshold)) => capture
+                .diff(baseline, query, &query_config, threshold)
+                .map(|diff| (diff.results, Some(diff.diffs))),
+            None => capture.run(query, &query_config).map(|results| (results, None)),
+        };
+        let (results, diffs) = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("query {} ({}) failed: {}", idx, query.name(), e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for result in diffs.iter().flatten() {
+            if let QueryResult::Diff(diff) = result {
+                if diff.verdict == Verdict::Regressed {
+                    eprintln!("query {} ({}) regressed: {}", idx, query.name(), result);
+                    regressions += 1;
+                }
+            }
+        }
+        writer.write(idx, query, diffs.as_ref().unwrap_or(&results))?;
 
-fn main() -> Result<(), TimelineError> {
+        if !query_config.budgets.is_empty() {
+            budgeted.push((idx, results));
+        }
+    }
+    writer.finish()?;
+
+    let checks = check_budgets(&query_config.budgets, &budgeted);
+    let over_budget = checks.iter().filter(|check| !check.passed()).count();
+    for check in &checks {
+        eprintln!("budget: {}", check);
+    }
+
+    if failed > 0 {
+        return Err(TimelineError::QueriesFailed {
+            failed,
+            total: query_config.queries.len(),
+        });
+    }
+    if regressions > 0 {
+        return Err(TimelineError::Regressions(regressions));
+    }
+    if over_budget > 0 {
+        return Err(TimelineError::OverBudget {
+            failed: over_budget,
+            total: checks.len(),
+        });
+    }
+
+    return Ok(());
+}
+
+fn main() {
     env_logger::init();
 
     info!("parsing opts");
 
     let opts = TimelineOpts::from_args();
 
-    info!("parsing tracks");
-    let tracks = parse_tracks(&opts)?;
+    if let Err(e) = run(opts) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    info!("parsing query config");
-    let query_config: QueryConfig = opts.query_file.parse()?;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use calculate_differences::query::types::Stat;
 
-    info!("parsing parse_zones");
-    let mut zones = parse_zones(&opts, &tracks)?;
+    fn fixture(name: &str) -> String {
+        return format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    }
 
-    info!("sorting zones");
-    zones.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+    #[test]
+    fn test_load_chrome_trace_with_default_opts() -> Result<(), TimelineError> {
+        let opts = TimelineOpts::from_iter(["timeline", "-f", &fixture("chrome_trace.json")]);
+        let capture = load(&opts)?;
 
-    info!("setting zone index");
-    set_zone_idx(&mut zones);
+        assert_eq!(
+            capture.tracks().iter().map(|t| t.name.as_str()).collect::<Vec<&str>>(),
+            vec!["CrRendererMain", "DedicatedWorker thread", "pid 2 tid 1"]
+        );
 
-    info!("running queries: zones: {}", zones.len());
-    for query in &query_config.queries {
-        info!("query: {:?}", query);
-        run_query(query, &query_config, &zones)?;
+        let stat: Stat = serde_json::from_str(r#"{"node": "RunTask"}"#).unwrap();
+        assert_eq!(
+            capture.stat(&stat)?.iter().map(|r| r.to_string()).collect::<Vec<String>>(),
+            vec!["RunTask,20000,10000,30000,CrRendererMain", "RunTask,4000,11000,15000,DedicatedWorker thread"]
+        );
+
+        return Ok(());
     }
 
-    return Ok(());
+    #[test]
+    fn test_load_chrome_trace_with_main_track() -> Result<(), TimelineError> {
+        let opts = TimelineOpts::from_iter(["timeline", "-f", &fixture("chrome_trace.json"), "-m", "CrRendererMain"]);
+        let capture = load(&opts)?;
+
+        assert_eq!(capture.tracks().len(), 1);
+        assert_eq!(capture.zones().len(), 2);
+
+        return Ok(());
+    }
 }
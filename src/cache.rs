@@ -0,0 +1,325 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    time::UNIX_EPOCH,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::{info, warn};
+
+use crate::{
+    error::TimelineError, parse::Telemetry, symbols::Symbols, tracks::Track, zones::Zone,
+};
+
+pub const CACHE_MAGIC: &[u8; 8] = b"TLCACHE\0";
+pub const CACHE_VERSION: u32 = 2;
+
+/// Identifies the csv a cache was built from.  If the csv's size or modified time no longer
+/// match, the cache is stale and gets ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceStamp {
+    pub len: u64,
+    pub modified: u64,
+}
+
+impl SourceStamp {
+    pub fn of(path: &str) -> Result<SourceStamp, TimelineError> {
+        let reading = |source| TimelineError::ReadFile {
+            file: path.to_string(),
+            source,
+        };
+        let metadata = fs::metadata(path).map_err(reading)?;
+        let modified = metadata
+            .modified()
+            .map_err(reading)?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        return Ok(SourceStamp {
+            len: metadata.len(),
+            modified,
+        });
+    }
+}
+
+pub fn cache_path(file: &str) -> String {
+    return format!("{}.timeline", file);
+}
+
+// FNV-1a, good enough to catch truncated or corrupted cache files.
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) -> Result<(), TimelineError> {
+    out.write_u32::<LittleEndian>(s.len() as u32)?;
+    out.write_all(s.as_bytes())?;
+    return Ok(());
+}
+
+fn read_str(input: &mut &[u8]) -> Result<String, TimelineError> {
+    let len = input.read_u32::<LittleEndian>()? as usize;
+    if input.len() < len {
+        return Err(TimelineError::InvalidCache("string runs past the end of the file".to_string()));
+    }
+
+    let (s, rest) = input.split_at(len);
+    *input = rest;
+
+    return String::from_utf8(s.to_vec())
+        .map_err(|_| TimelineError::InvalidCache("string is not utf8".to_string()));
+}
+
+/// Writes the tracks and the zones, sorted by start time, along with the table of zone names and
+/// how many malformed rows the parse skipped, so a cached lenient parse still reports them.
+///
+/// Layout, all little endian:
+///     magic, version: u32, source len: u64, source modified: u64, payload len: u64, checksum: u64
+///     skipped rows: u64
+///     names: u32 count, then u32 len + bytes each
+///     tracks: u32 count, then u64 id + u32 len + bytes each
+///     zones: u64 count, then u32 name, u64 track, u64 start, u64 end, u64 csv offset each
+pub fn write_cache<W: Write>(telemetry: &Telemetry, source: SourceStamp, out: W) -> Result<(), TimelineError> {
+    let mut zones = telemetry.zones.iter().collect::<Vec<&Zone>>();
+    zones.sort_by_key(|z| (z.start_time, std::cmp::Reverse(z.end_time)));
+
+    let mut payload: Vec<u8> = vec![];
+
+    payload.write_u64::<LittleEndian>(telemetry.skipped_rows as u64)?;
+
+    payload.write_u32::<LittleEndian>(telemetry.names.len() as u32)?;
+    for (_, name) in telemetry.names.iter() {
+        write_str(&mut payload, name)?;
+    }
+
+    payload.write_u32::<LittleEndian>(telemetry.tracks.len() as u32)?;
+    for track in &telemetry.tracks {
+        payload.write_u64::<LittleEndian>(track.id as u64)?;
+        write_str(&mut payload, &track.name)?;
+    }
+
+    payload.write_u64::<LittleEndian>(zones.len() as u64)?;
+    for zone in zones {
+        payload.write_u32::<LittleEndian>(zone.name.id() as u32)?;
+        payload.write_u64::<LittleEndian>(zone.track_id as u64)?;
+        payload.write_u64::<LittleEndian>(zone.start_time)?;
+        payload.write_u64::<LittleEndian>(zone.end_time)?;
+        payload.write_u64::<LittleEndian>(zone.csv_offset)?;
+    }
+
+    let mut out = BufWriter::new(out);
+    out.write_all(CACHE_MAGIC)?;
+    out.write_u32::<LittleEndian>(CACHE_VERSION)?;
+    out.write_u64::<LittleEndian>(source.len)?;
+    out.write_u64::<LittleEndian>(source.modified)?;
+    out.write_u64::<LittleEndian>(payload.len() as u64)?;
+    out.write_u64::<LittleEndian>(checksum(&payload))?;
+    out.write_all(&payload)?;
+    out.flush()?;
+
+    return Ok(());
+}
+
+/// Reads a cache back, failing if it was written by another version, is corrupted, or was built
+/// from a different source than `source`.
+pub fn read_cache<R: Read>(mut input: R, source: SourceStamp) -> Result<Telemetry, TimelineError> {
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != CACHE_MAGIC {
+        return Err(TimelineError::InvalidCache("not a timeline cache".to_string()));
+    }
+
+    let version = input.read_u32::<LittleEndian>()?;
+    if version != CACHE_VERSION {
+        return Err(TimelineError::InvalidCache(format!(
+            "version {} but expected {}",
+            version, CACHE_VERSION
+        )));
+    }
+
+    let stamp = SourceStamp {
+        len: input.read_u64::<LittleEndian>()?,
+        modified: input.read_u64::<LittleEndian>()?,
+    };
+    if stamp != source {
+        return Err(TimelineError::InvalidCache("the source csv has changed".to_string()));
+    }
+
+    let payload_len = input.read_u64::<LittleEndian>()? as usize;
+    let expected_checksum = input.read_u64::<LittleEndian>()?;
+
+    let mut payload = vec![];
+    input.read_to_end(&mut payload)?;
+    if payload.len() != payload_len || checksum(&payload) != expected_checksum {
+        return Err(TimelineError::InvalidCache("checksum mismatch".to_string()));
+    }
+
+    let mut input = payload.as_slice();
+
+    let skipped_rows = input.read_u64::<LittleEndian>()? as usize;
+
+    let name_count = input.read_u32::<LittleEndian>()? as usize;
+    // names were written in id order, so interning them again hands out the same ids.
+    let mut names = Symbols::default();
+    let mut symbols = Vec::with_capacity(name_count);
+    for _ in 0..name_count {
+        symbols.push(names.intern(&read_str(&mut input)?));
+    }
+
+    let track_count = input.read_u32::<LittleEndian>()? as usize;
+    let mut tracks = Vec::with_capacity(track_count);
+    for _ in 0..track_count {
+        let id = input.read_u64::<LittleEndian>()? as usize;
+        tracks.push(Track {
+            id,
+            name: read_str(&mut input)?,
+        });
+    }
+
+    let zone_count = input.read_u64::<LittleEndian>()? as usize;
+    let mut zones = Vec::with_capacity(zone_count);
+    for idx in 0..zone_count {
+        let name = *symbols
+            .get(input.read_u32::<LittleEndian>()? as usize)
+            .ok_or_else(|| TimelineError::InvalidCache("unknown zone name".to_string()))?;
+        let track_id = input.read_u64::<LittleEndian>()? as usize;
+        let start_time = input.read_u64::<LittleEndian>()?;
+        let end_time = input.read_u64::<LittleEndian>()?;
+
+        let mut zone = Zone::new(name, start_time, end_time, track_id);
+        zone.csv_offset = input.read_u64::<LittleEndian>()?;
+        zone.idx = idx;
+        zones.push(zone);
+    }
+
+    return Ok(Telemetry { tracks, zones, names, skipped_rows });
+}
+
+pub fn write_cache_file(telemetry: &Telemetry, file: &str, output: &str) -> Result<(), TimelineError> {
+    info!("writing cache for {} to {}", file, output);
+    let source = SourceStamp::of(file)?;
+
+    return File::create(output)
+        .map_err(TimelineError::from)
+        .and_then(|out| write_cache(telemetry, source, out))
+        .map_err(|e| match e {
+            TimelineError::Io(source) => TimelineError::WriteFile {
+                file: output.to_string(),
+                source,
+            },
+            e => e,
+        });
+}
+
+/// Loads the cache next to `file` if there is one and it is still valid for `file`.
+pub fn load_cache_file(file: &str) -> Option<Telemetry> {
+    let path = cache_path(file);
+    let cache = File::open(&path).ok()?;
+
+    let telemetry = SourceStamp::of(file).and_then(|source| read_cache(cache, source));
+    return match telemetry {
+        Ok(telemetry) => {
+            info!("loaded cache {}", path);
+            Some(telemetry)
+        }
+        Err(e) => {
+            warn!("ignoring cache {}: {}", path, e);
+            None
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn telemetry() -> Telemetry {
+        let mut names = Symbols::default();
+        let mut zones = vec![
+            Zone::new(names.intern("cache::b"), 20, 30, 1),
+            Zone::new(names.intern("cache::a"), 10, 50, 1),
+            Zone::new(names.intern("cache::a"), 12, 14, 2),
+        ];
+        zones[1].csv_offset = 42;
+
+        return Telemetry {
+            tracks: vec![
+                Track { name: "Main Thread".to_string(), id: 1 },
+                Track { name: "Instrumentation".to_string(), id: 2 },
+            ],
+            zones,
+            names,
+            skipped_rows: 3,
+        };
+    }
+
+    const SOURCE: SourceStamp = SourceStamp { len: 1024, modified: 7 };
+
+    #[test]
+    fn test_cache_round_trip() -> Result<(), TimelineError> {
+        let mut bytes = vec![];
+        write_cache(&telemetry(), SOURCE, &mut bytes)?;
+
+        let read = read_cache(bytes.as_slice(), SOURCE)?;
+
+        assert_eq!(read.tracks.len(), 2);
+        assert_eq!(read.tracks[1].name, "Instrumentation");
+        assert_eq!(
+            read.zones
+                .iter()
+                .map(|z| (read.names.name(z.name), z.start_time, z.end_time, z.track_id, z.idx))
+                .collect::<Vec<(&str, u64, u64, usize, usize)>>(),
+            vec![
+                ("cache::a", 10, 50, 1, 0),
+                ("cache::a", 12, 14, 2, 1),
+                ("cache::b", 20, 30, 1, 2),
+            ]
+        );
+        assert_eq!(read.zones[0].csv_offset, 42);
+        assert_eq!(read.skipped_rows, 3);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_cache_invalidation() -> Result<(), TimelineError> {
+        let mut bytes = vec![];
+        write_cache(&telemetry(), SOURCE, &mut bytes)?;
+
+        let changed = SourceStamp { len: 1025, modified: 7 };
+        assert!(matches!(read_cache(bytes.as_slice(), changed), Err(TimelineError::InvalidCache(_))));
+
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        assert!(matches!(read_cache(corrupted.as_slice(), SOURCE), Err(TimelineError::InvalidCache(_))));
+
+        let mut old_version = bytes.clone();
+        old_version[8] = 0;
+        assert!(matches!(read_cache(old_version.as_slice(), SOURCE), Err(TimelineError::InvalidCache(_))));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_file_errors_name_the_file() {
+        let missing = std::env::temp_dir().join("timeline_missing_dir").join("capture.csv").display().to_string();
+        match SourceStamp::of(&missing) {
+            Err(TimelineError::ReadFile { file, .. }) => assert_eq!(file, missing),
+            other => panic!("expected a read error, got {:?}", other),
+        }
+
+        let source = std::env::current_exe().unwrap().display().to_string();
+        let output = format!("{}.timeline", missing);
+        match write_cache_file(&telemetry(), &source, &output) {
+            Err(TimelineError::WriteFile { file, .. }) => assert_eq!(file, output),
+            other => panic!("expected a write error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::zones::Zone;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct CallNode {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    depth: usize,
+}
+
+/// The nesting of zones on each track.  A zone's parent is the innermost zone on the same track
+/// that contains it, zones without a parent are roots at depth 0.
+///
+/// Nodes are indexed by zone idx, and children are kept in start order.
+#[derive(Debug, Default)]
+pub struct CallTree {
+    nodes: Vec<CallNode>,
+}
+
+impl CallTree {
+    /// Expects the zones to be sorted by start time, with the longer zone first on ties, and to
+    /// have their idx set.
+    pub fn new(zones: &[Zone]) -> CallTree {
+        let mut nodes = vec![CallNode::default(); zones.len()];
+        let mut stacks: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for zone in zones {
+            let stack = stacks.entry(zone.track_id).or_default();
+
+            while let Some(top) = stack.last() {
+                if zones[*top].contains(zone) {
+                    break;
+                }
+                stack.pop();
+            }
+
+            if let Some(parent) = stack.last() {
+                let parent = *parent;
+                nodes[zone.idx].parent = Some(parent);
+                nodes[zone.idx].depth = nodes[parent].depth + 1;
+                nodes[parent].children.push(zone.idx);
+            }
+
+            stack.push(zone.idx);
+        }
+
+        return CallTree { nodes };
+    }
+
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        return self.nodes[idx].parent;
+    }
+
+    pub fn children(&self, idx: usize) -> &[usize] {
+        return &self.nodes[idx].children;
+    }
+
+    pub fn depth(&self, idx: usize) -> usize {
+        return self.nodes[idx].depth;
+    }
+
+    /// Every enclosing zone, innermost first.
+    pub fn ancestors(&self, idx: usize) -> Vec<usize> {
+        let mut out = vec![];
+        let mut curr = self.parent(idx);
+
+        while let Some(parent) = curr {
+            out.push(parent);
+            curr = self.parent(parent);
+        }
+
+        return out;
+    }
+
+    /// Every zone nested under idx, in start order.
+    pub fn descendants(&self, idx: usize) -> Vec<usize> {
+        let mut out = vec![];
+        let mut stack = self.children(idx).iter().rev().copied().collect::<Vec<usize>>();
+
+        while let Some(curr) = stack.pop() {
+            out.push(curr);
+            stack.extend(self.children(curr).iter().rev());
+        }
+
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{tests::TestZones, zone_search::Zones};
+
+    #[test]
+    fn test_call_tree() {
+        let zones = Zones::named(&[
+            ("root", 0, 100, 0),
+            ("a", 10, 50, 0),
+            ("a.a", 20, 30, 0),
+            ("other track", 25, 60, 1),
+            ("b", 60, 90, 0),
+            ("b.a", 60, 70, 0),
+        ]);
+        let idx = |name: &str| zones.by_name(name)[0];
+        let tree = zones.call_tree();
+
+        assert_eq!(tree.parent(idx("root")), None);
+        assert_eq!(tree.children(idx("root")), &[idx("a"), idx("b")]);
+        assert_eq!(tree.children(idx("b")), &[idx("b.a")]);
+        assert_eq!(tree.depth(idx("a.a")), 2);
+        assert_eq!(tree.parent(idx("other track")), None);
+        assert_eq!(tree.ancestors(idx("a.a")), vec![idx("a"), idx("root")]);
+        assert_eq!(
+            tree.descendants(idx("root")),
+            vec![idx("a"), idx("a.a"), idx("b"), idx("b.a")]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    fmt::Debug,
+    io::{Read, Seek},
+};
+
+use crate::{
+    chrome::{parse_chrome_trace, ChromeRows},
+    error::TimelineError,
+    parse::{parse_telemetry, CsvRows, RowSource, Telemetry},
+    query::{
+        aggregate_query, cost_query, diff_results, export_query, histogram_query, reduce_query, run_query,
+        self_time_query, stat_query,
+        types::{
+            Aggregate, Cost, DiffThreshold, Export, Histogram, Query, QueryResult, Reduce, SelfTime, Stat,
+        },
+        query_config::QueryConfig,
+        with_window,
+    },
+    tracks::Track,
+    zone_search::Zones,
+};
+
+/// The results of a query on a capture, and how they changed from the results of the same query
+/// on a baseline.
+#[derive(Debug)]
+pub struct QueryDiff {
+    pub results: Vec<QueryResult>,
+    pub diffs: Vec<QueryResult>,
+}
+
+/// A whole capture held in memory: its tracks, its zones sorted and indexed for searching, and
+/// where to read the original rows back from.  Every query is a method, and every method applies
+/// the query's window the same way the query file does.
+#[derive(Debug)]
+pub struct Capture {
+    tracks: Vec<Track>,
+    zones: Zones,
+    rows: Box<dyn RowSource>,
+    skipped_rows: usize,
+}
+
+impl Capture {
+    pub fn new(telemetry: Telemetry, rows: Box<dyn RowSource>) -> Capture {
+        return Capture {
+            tracks: telemetry.tracks,
+            zones: Zones::new(telemetry.zones, telemetry.names),
+            rows,
+            skipped_rows: telemetry.skipped_rows,
+        };
+    }
+
+    /// Reads tms_to_csv output.  The reader is rewound and kept afterwards, queries returning
+    /// original rows read them back from it.  `source` is only used to say where a malformed row
+    /// is, see `parse_telemetry`.
+    pub fn from_csv<R: Read + Seek + Debug + Send + 'static>(
+        mut reader: R,
+        source: &str,
+        lenient: bool,
+    ) -> Result<Capture, TimelineError> {
+        let telemetry = parse_telemetry(&mut reader, source, lenient)?;
+        reader.rewind()?;
+
+        return Ok(Capture::new(telemetry, Box::new(CsvRows::new(reader))));
+    }
+
+    /// Reads a Chrome Trace Event Format json, see `parse_chrome_trace`.
+    pub fn from_chrome_trace<R: Read>(reader: R, source: &str, lenient: bool) -> Result<Capture, TimelineError> {
+        return Ok(Capture::new(parse_chrome_trace(reader, source, lenient)?, Box::new(ChromeRows)));
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        return &self.tracks;
+    }
+
+    pub fn zones(&self) -> &Zones {
+        return &self.zones;
+    }
+
+    /// Malformed rows skipped by a lenient parse.
+    pub fn skipped_rows(&self) -> usize {
+        return self.skipped_rows;
+    }
+
+    pub fn run(&self, query: &Query, config: &QueryConfig) -> Result<Vec<QueryResult>, TimelineError> {
+        return run_query(query, config, &self.tracks, &self.zones, self.rows.as_ref());
+    }
+
+    /// Runs the query on both captures and compares the results, see `diff_results`.  Queries
+    /// whose results can't be compared fail without running, see `Query::comparable`, and so
+    /// does a query that has no samples in either capture.
+    pub fn diff(
+        &self,
+        baseline: &Capture,
+        query: &Query,
+        config: &QueryConfig,
+        threshold: &DiffThreshold,
+    ) -> Result<QueryDiff, TimelineError> {
+        if !query.comparable() {
+            return Err(TimelineError::NotComparable { query: query.name() });
+        }
+
+        let results = self.run(query, config)?;
+        let diffs = diff_results(&baseline.run(query, config)?, &results, threshold);
+        if diffs.is_empty() {
+            return Err(TimelineError::NothingToCompare { query: query.name() });
+        }
+
+        return Ok(QueryDiff { results, diffs });
+    }
+
+    pub fn self_time(&self, query: &SelfTime, config: &QueryConfig) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("SelfTime", &query.window, &self.zones, |zones| {
+            return self_time_query(query, config, &self.tracks, zones);
+        });
+    }
+
+    pub fn reduce(&self, query: &Reduce) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Reduce", &query.window, &self.zones, |zones| {
+            return reduce_query(query, zones, self.rows.as_ref());
+        });
+    }
+
+    pub fn stat(&self, query: &Stat) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Stat", &query.window, &self.zones, |zones| {
+            return stat_query(query, &self.tracks, zones);
+        });
+    }
+
+    pub fn cost(&self, query: &Cost, config: &QueryConfig) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Cost", &query.window, &self.zones, |zones| {
+            return cost_query(query, config, &self.tracks, zones, self.rows.as_ref());
+        });
+    }
+
+    pub fn export(&self, query: &Export) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Export", &query.window, &self.zones, |zones| {
+            return export_query(query, &self.tracks, zones);
+        });
+    }
+
+    pub fn aggregate(&self, query: &Aggregate, config: &QueryConfig) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Aggregate", &query.window, &self.zones, |zones| {
+            return aggregate_query(query, config, &self.tracks, zones, self.rows.as_ref());
+        });
+    }
+
+    pub fn histogram(&self, query: &Histogram, config: &QueryConfig) -> Result<Vec<QueryResult>, TimelineError> {
+        return with_window("Histogram", &query.window, &self.zones, |zones| {
+            return histogram_query(query, config, &self.tracks, zones, self.rows.as_ref());
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    const CSV: &str = "\
+TM_TRACK,1,Main Thread,0
+TM_ZONE,1,child,30,40,0
+TM_ZONE,1,root,10,50,0
+TM_ZONE,1,child,60,65,0
+";
+
+    fn config(ignores: &[&str]) -> QueryConfig {
+        return QueryConfig {
+            ignores: ignores.iter().map(|name| (*name).into()).collect(),
+            ..QueryConfig::default()
+        };
+    }
+
+    #[test]
+    fn test_capture_from_csv() -> Result<(), TimelineError> {
+        let capture = Capture::from_csv(Cursor::new(CSV.as_bytes().to_vec()), "test.csv", false)?;
+
+        assert_eq!(capture.tracks().len(), 1);
+        assert_eq!(
+            capture.zones().iter().map(|z| capture.zones().name(z)).collect::<Vec<&str>>(),
+            vec!["root", "child", "child"]
+        );
+
+        let stat: Stat = serde_json::from_str(r#"{"node": "child", "window": {"to": 50}}"#).unwrap();
+        assert_eq!(
+            capture.stat(&stat)?.iter().map(|r| r.to_string()).collect::<Vec<String>>(),
+            vec!["child,10,30,40,Main Thread"]
+        );
+
+        let reduce: Query = serde_json::from_str(r#"{"type": "Reduce", "node": "root"}"#).unwrap();
+        assert_eq!(
+            capture.run(&reduce, &config(&[]))?.iter().map(|r| r.to_string()).collect::<Vec<String>>(),
+            vec!["TM_ZONE,1,child,30,40,0", "TM_ZONE,1,root,10,50,0"]
+        );
+
+        let self_time: SelfTime = serde_json::from_str(r#"{"node": "root", "partial_ignore": []}"#).unwrap();
+        assert_eq!(
+            capture.self_time(&self_time, &config(&["child"]))?.iter().map(|r| r.to_string()).collect::<Vec<String>>(),
+            vec!["SelfTime,root,30,,Main Thread,"]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_capture_diff() -> Result<(), TimelineError> {
+        let baseline = Capture::from_csv(Cursor::new(CSV.as_bytes().to_vec()), "baseline.csv", false)?;
+        let capture = Capture::from_csv(
+            Cursor::new(CSV.replace("TM_ZONE,1,child,60,65,0", "TM_ZONE,1,child,60,75,0").into_bytes()),
+            "test.csv",
+            false,
+        )?;
+
+        let stat: Query = serde_json::from_str(r#"{"type": "Stat", "node": "child"}"#).unwrap();
+        let diff = capture.diff(&baseline, &stat, &config(&[]), &DiffThreshold::default())?;
+
+        assert_eq!(diff.results.len(), 2);
+        match diff.diffs.as_slice() {
+            [QueryResult::Diff(d)] => assert_eq!((d.name.as_str(), d.median.delta), ("child", 5.0)),
+            _ => unreachable!(),
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_capture_diff_not_comparable() -> Result<(), TimelineError> {
+        let baseline = Capture::from_csv(Cursor::new(CSV.as_bytes().to_vec()), "baseline.csv", false)?;
+        let capture = Capture::from_csv(Cursor::new(CSV.as_bytes().to_vec()), "test.csv", false)?;
+        let output = std::env::temp_dir().join("timeline_test_capture_diff.json").display().to_string();
+
+        let export: Query = serde_json::from_str(&format!(r#"{{"type": "Export", "output": {:?}}}"#, output)).unwrap();
+        assert!(matches!(
+            capture.diff(&baseline, &export, &config(&[]), &DiffThreshold::default()),
+            Err(TimelineError::NotComparable { query: "Export" })
+        ));
+        assert!(!std::path::Path::new(&output).exists());
+
+        let cost: Query = serde_json::from_str(r#"{"type": "Cost", "node": "child"}"#).unwrap();
+        assert!(matches!(
+            capture.diff(&baseline, &cost, &config(&[]), &DiffThreshold::default()),
+            Err(TimelineError::NothingToCompare { query: "Cost" })
+        ));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_capture_from_chrome_trace() -> Result<(), TimelineError> {
+        let json = r#"[{"name": "a", "ph": "X", "ts": 1, "dur": 2, "pid": 1, "tid": 1}]"#;
+        let capture = Capture::from_chrome_trace(json.as_bytes(), "trace.json", false)?;
+
+        let stat: Stat = serde_json::from_str(r#"{"node": "a"}"#).unwrap();
+        assert_eq!(capture.stat(&stat)?.len(), 1);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_capture_is_send_and_sync() {
+        fn send_and_sync<T: Send + Sync>() {}
+        send_and_sync::<Capture>();
+    }
+}
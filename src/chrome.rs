@@ -0,0 +1,520 @@
+use std::{collections::{HashMap, HashSet}, io::{Read, Write}};
+
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{error::{RowError, TimelineError}, parse::{RowSource, Telemetry}, symbols::Symbols, tracks::{track_name, Track}, zones::Zone};
+
+/// The fields of a trace event needed to rebuild zones.  Everything else (cat, args other than
+/// thread names, ...) is ignored.
+#[derive(Debug)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    ph: &'a str,
+    ts: Option<u64>,
+    dur: Option<f64>,
+    thread: (String, String),
+    args: &'a Value,
+}
+
+// a string field, `None` when the event doesn't have it.
+fn string_field<'a>(event: &'a Value, field: &'static str) -> Result<Option<&'a str>, RowError> {
+    return match event.get(field) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(value) => Err(RowError {
+            field,
+            reason: format!("{} is not a string", value),
+        }),
+    };
+}
+
+fn number_field(event: &Value, field: &'static str) -> Result<Option<f64>, RowError> {
+    return match event.get(field) {
+        None => Ok(None),
+        Some(Value::Number(value)) => Ok(value.as_f64()),
+        Some(value) => Err(RowError {
+            field,
+            reason: format!("{} is not a number", value),
+        }),
+    };
+}
+
+// a timestamp in nanoseconds, zones can't start before 0 or after the end of time.
+fn timestamp(ts: f64) -> Result<u64, RowError> {
+    if ts < 0.0 {
+        return Err(RowError {
+            field: "ts",
+            reason: format!("{} is negative", ts),
+        });
+    }
+    if ts * 1000.0 > u64::MAX as f64 {
+        return Err(RowError {
+            field: "ts",
+            reason: format!("{} overflows", ts),
+        });
+    }
+
+    return Ok(to_nanos(ts));
+}
+
+fn read_event(event: &Value) -> Result<TraceEvent<'_>, RowError> {
+    if !event.is_object() {
+        return Err(RowError {
+            field: "event",
+            reason: format!("{} is not an object", event),
+        });
+    }
+
+    let ph = string_field(event, "ph")?.ok_or_else(|| RowError {
+        field: "ph",
+        reason: "missing".to_string(),
+    })?;
+    let thread = (
+        event.get("pid").unwrap_or(&Value::Null).to_string(),
+        event.get("tid").unwrap_or(&Value::Null).to_string(),
+    );
+
+    return Ok(TraceEvent {
+        name: string_field(event, "name")?.unwrap_or(""),
+        ph,
+        ts: number_field(event, "ts")?.map(timestamp).transpose()?,
+        dur: number_field(event, "dur")?,
+        thread,
+        args: event.get("args").unwrap_or(&Value::Null),
+    });
+}
+
+// the events of a trace, either the bare array or the object form with `traceEvents`.
+fn trace_events(trace: Value) -> Result<Vec<Value>, String> {
+    return match trace {
+        Value::Array(events) => Ok(events),
+        Value::Object(mut trace) => match trace.remove("traceEvents") {
+            Some(Value::Array(events)) => Ok(events),
+            Some(_) => Err("traceEvents is not an array".to_string()),
+            None => Err("no traceEvents".to_string()),
+        },
+        _ => Err("expected an array of events or an object with traceEvents".to_string()),
+    };
+}
+
+// trace timestamps are in microseconds, zones are in nanoseconds.
+fn to_nanos(micros: f64) -> u64 {
+    return (micros * 1000.0).round() as u64;
+}
+
+// where a complete event ends, `dur` can't be negative or run past the end of time.
+fn end_of(start: u64, dur: f64) -> Result<u64, RowError> {
+    if dur < 0.0 {
+        return Err(RowError {
+            field: "dur",
+            reason: format!("{} is negative", dur),
+        });
+    }
+
+    return start.checked_add(to_nanos(dur)).ok_or_else(|| RowError {
+        field: "dur",
+        reason: format!("{} overflows the end time", dur),
+    });
+}
+
+// pops the begin an end event closes, the innermost one open on its thread.  An end that has a
+// name has to be closing a begin of that name, and can't come before it.
+fn close_begin(open: &mut Vec<(String, u64)>, name: &str, end: u64) -> Result<Option<(String, u64)>, RowError> {
+    let (begin, start) = match open.last() {
+        Some(begin) => begin,
+        None => return Ok(None),
+    };
+
+    if !name.is_empty() && name != begin {
+        return Err(RowError {
+            field: "name",
+            reason: format!("ends {} but the open begin is {}", name, begin),
+        });
+    }
+    if end < *start {
+        return Err(RowError {
+            field: "ts",
+            reason: format!("ends {} before it began", begin),
+        });
+    }
+
+    return Ok(open.pop());
+}
+
+/// Reads a Chrome Trace Event Format file, either the bare array of events or the object form
+/// with `traceEvents`.  Every pid/tid pair with zones becomes a track, named after its
+/// `thread_name` metadata event when there is one.  Complete (`X`) events and matched `B`/`E`
+/// pairs become zones, unmatched begins are dropped.  An event is malformed when a field has the
+/// wrong type, it has no `ph`, a zone event has no `ts` or a negative one, or an `E`'s name
+/// differs from the begin it closes or it ends before it.  Malformed events fail the parse, or
+/// with `lenient` are skipped and counted, `source` is only used to say where they are.
+pub fn parse_chrome_trace<R: Read>(reader: R, source: &str, lenient: bool) -> Result<Telemetry, TimelineError> {
+    let invalid = |reason: String| TimelineError::InvalidTrace {
+        file: source.to_string(),
+        reason,
+    };
+    let trace: Value = serde_json::from_reader(reader).map_err(|e| invalid(e.to_string()))?;
+    let events = trace_events(trace).map_err(invalid)?;
+
+    let mut track_ids: HashMap<(String, String), usize> = HashMap::new();
+    let mut thread_names: HashMap<(String, String), String> = HashMap::new();
+    let mut open: HashMap<usize, Vec<(String, u64)>> = HashMap::new();
+    let mut zones: Vec<Zone> = vec![];
+    let mut names = Symbols::default();
+    let mut malformed = 0;
+
+    for (idx, event) in events.iter().enumerate() {
+        let parsed = read_event(event).and_then(|event| {
+            if event.ph == "M" {
+                if event.name == "thread_name" {
+                    if let Some(name) = event.args.get("name").and_then(|name| name.as_str()) {
+                        thread_names.insert(event.thread, name.to_string());
+                    }
+                }
+                return Ok(());
+            }
+            if !matches!(event.ph, "X" | "B" | "E") {
+                return Ok(());
+            }
+
+            let ts = event.ts.ok_or_else(|| RowError {
+                field: "ts",
+                reason: "missing".to_string(),
+            })?;
+            let next_id = track_ids.len() + 1;
+            let track_id = *track_ids.entry(event.thread).or_insert(next_id);
+
+            match event.ph {
+                "X" => {
+                    let end = end_of(ts, event.dur.unwrap_or(0.0))?;
+                    zones.push(Zone::new(names.intern(event.name), ts, end, track_id));
+                }
+                "B" => open.entry(track_id).or_default().push((event.name.to_string(), ts)),
+                _ => match close_begin(open.entry(track_id).or_default(), event.name, ts)? {
+                    Some((name, start)) => zones.push(Zone::new(names.intern(&name), start, ts, track_id)),
+                    None => debug!("dropping end event without a begin: {}", event.name),
+                },
+            }
+
+            return Ok(());
+        });
+
+        if let Err(error) = parsed {
+            let error = TimelineError::MalformedEvent {
+                file: source.to_string(),
+                event: idx,
+                error,
+            };
+
+            if !lenient {
+                return Err(error);
+            }
+            warn!("skipping {}", error);
+            malformed += 1;
+        }
+    }
+
+    let dropped: usize = open.values().map(|stack| stack.len()).sum();
+    if dropped > 0 {
+        debug!("dropping {} begin events that never ended", dropped);
+    }
+
+    // threads whose only zone events were dropped, begins that never ended, have nothing to show.
+    let with_zones = zones.iter().map(|z| z.track_id).collect::<HashSet<usize>>();
+    let mut tracks = track_ids
+        .into_iter()
+        .filter(|(_, id)| with_zones.contains(id))
+        .map(|((pid, tid), id)| Track {
+            name: thread_names
+                .remove(&(pid.clone(), tid.clone()))
+                .unwrap_or_else(|| format!("pid {} tid {}", pid, tid)),
+            id,
+        })
+        .collect::<Vec<Track>>();
+    tracks.sort_by_key(|track| track.id);
+
+    return Ok(Telemetry { tracks, zones, names, skipped_rows: malformed });
+}
+
+#[derive(Debug, Serialize)]
+struct CompleteEvent<'a> {
+    name: &'a str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: usize,
+    tid: usize,
+}
+
+// nanoseconds back to the microseconds trace events use.
+fn to_micros(nanos: u64) -> f64 {
+    return nanos as f64 / 1000.0;
+}
+
+/// Writes zones, named in `names`, as Chrome Trace Event Format json.  Every track becomes a
+/// thread of a single process, named with a thread_name metadata event.  Timestamps are written relative to the
+/// earliest zone so they keep their sub microsecond precision, the absolute start is kept in
+/// `otherData.base_ns`.
+pub fn write_chrome_trace<'a, W: Write>(
+    zones: impl Iterator<Item = &'a Zone>,
+    names: &Symbols,
+    tracks: &[Track],
+    mut out: W,
+) -> Result<(), TimelineError> {
+    let zones = zones.collect::<Vec<&Zone>>();
+    let base = zones.iter().map(|z| z.start_time).min().unwrap_or(0);
+
+    let mut track_ids = zones.iter().map(|z| z.track_id).collect::<Vec<usize>>();
+    track_ids.sort();
+    track_ids.dedup();
+
+    let mut events = track_ids
+        .iter()
+        .map(|id| {
+            return json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 1,
+                "tid": id,
+                "args": { "name": track_name(tracks, *id) },
+            });
+        })
+        .collect::<Vec<Value>>();
+
+    for zone in zones {
+        events.push(serde_json::to_value(CompleteEvent {
+            name: names.name(zone.name),
+            cat: "timeline",
+            ph: "X",
+            ts: to_micros(zone.start_time - base),
+            dur: to_micros(zone.duration),
+            pid: 1,
+            tid: zone.track_id,
+        })?);
+    }
+
+    serde_json::to_writer(&mut out, &json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ns",
+        "otherData": { "base_ns": base },
+    }))?;
+    out.flush()?;
+
+    return Ok(());
+}
+
+/// Trace events have no csv row to give back, so rows are written in the TM_ZONE layout from the
+/// zone itself.
+#[derive(Debug)]
+pub struct ChromeRows;
+
+impl RowSource for ChromeRows {
+    fn row(&self, zone: &Zone, names: &Symbols) -> Result<String, TimelineError> {
+        return Ok(format!(
+            "TM_ZONE,{},{},{},{}",
+            zone.track_id,
+            names.name(zone.name),
+            zone.start_time,
+            zone.end_time
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{tests::TestZones, zone_search::Zones};
+
+    #[test]
+    fn test_parse_chrome_trace() -> Result<(), TimelineError> {
+        let json = r#"{
+            "traceEvents": [
+                {"name": "thread_name", "ph": "M", "pid": 1, "tid": 7, "args": {"name": "CrRendererMain"}},
+                {"name": "RunTask", "ph": "B", "ts": 10.0, "pid": 1, "tid": 7},
+                {"name": "FunctionCall", "ph": "X", "ts": 12.5, "dur": 3.0, "pid": 1, "tid": 7},
+                {"name": "GC", "ph": "B", "ts": 20, "pid": 1, "tid": 7},
+                {"ph": "E", "ts": 25, "pid": 1, "tid": 7},
+                {"name": "RunTask", "ph": "E", "ts": 30, "pid": 1, "tid": 7},
+                {"name": "Worker", "ph": "X", "ts": 11, "dur": 1, "pid": 1, "tid": "worker"},
+                {"name": "NeverEnds", "ph": "B", "ts": 40, "pid": 1, "tid": 7}
+            ]
+        }"#;
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", false)?;
+
+        assert_eq!(
+            telemetry.tracks.iter().map(|t| t.name.as_str()).collect::<Vec<&str>>(),
+            vec!["CrRendererMain", "pid 1 tid \"worker\""]
+        );
+
+        let zones = Zones::new(telemetry.zones, telemetry.names);
+        assert_eq!(
+            zones
+                .iter()
+                .map(|z| (zones.name(z), z.start_time, z.end_time, z.track_id))
+                .collect::<Vec<(&str, u64, u64, usize)>>(),
+            vec![
+                ("RunTask", 10_000, 30_000, 1),
+                ("Worker", 11_000, 12_000, 2),
+                ("FunctionCall", 12_500, 15_500, 1),
+                ("GC", 20_000, 25_000, 1),
+            ]
+        );
+        assert_eq!(zones.call_tree().children(0), &[2, 3]);
+        assert_eq!(ChromeRows.row(&zones[3], zones.names())?, "TM_ZONE,1,GC,20000,25000");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_chrome_trace_round_trip() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("root", 1_642_630_333_023_839_586, 1_642_630_333_023_880_460, 65536),
+            ("child", 1_642_630_333_023_852_127, 1_642_630_333_023_877_210, 65536),
+            ("other", 1_642_630_333_023_852_127, 1_642_630_333_023_852_500, 3),
+        ]);
+        let tracks = vec![Track { name: "Main Thread".to_string(), id: 65536 }];
+
+        let mut out = vec![];
+        write_chrome_trace(zones.iter(), zones.names(), &tracks, &mut out)?;
+
+        let telemetry = parse_chrome_trace(out.as_slice(), "trace.json", false)?;
+        assert_eq!(
+            telemetry.tracks.iter().map(|t| t.name.as_str()).collect::<Vec<&str>>(),
+            vec!["Main Thread", "3"]
+        );
+
+        let base = 1_642_630_333_023_839_586;
+        assert_eq!(
+            telemetry
+                .zones
+                .iter()
+                .map(|z| (telemetry.names.name(z.name), z.start_time + base, z.duration))
+                .collect::<Vec<(&str, u64, u64)>>(),
+            zones
+                .iter()
+                .map(|z| (zones.name(z), z.start_time, z.duration))
+                .collect::<Vec<(&str, u64, u64)>>()
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_parse_chrome_trace_array() -> Result<(), TimelineError> {
+        let json = r#"[{"name": "a", "ph": "X", "ts": 1, "dur": 2, "pid": 1, "tid": 1}]"#;
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", false)?;
+
+        assert_eq!(telemetry.tracks.len(), 1);
+        assert_eq!(telemetry.zones.len(), 1);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_malformed_duration() {
+        let json = r#"[
+            {"name": "a", "ph": "X", "ts": 1, "dur": 2, "pid": 1, "tid": 1},
+            {"name": "overflow", "ph": "X", "ts": 18446744073709551, "dur": 1, "pid": 1, "tid": 1},
+            {"name": "negative", "ph": "X", "ts": 5, "dur": -2, "pid": 1, "tid": 1}
+        ]"#;
+
+        match parse_chrome_trace(json.as_bytes(), "trace.json", false) {
+            Err(e) => assert_eq!(e.to_string(), "trace.json: malformed trace event 1, dur: 1 overflows the end time"),
+            Ok(_) => unreachable!(),
+        }
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", true).unwrap();
+        assert_eq!(telemetry.zones.len(), 1);
+        assert_eq!(telemetry.skipped_rows, 2);
+    }
+
+    #[test]
+    fn test_mismatched_end() {
+        let json = r#"[
+            {"name": "outer", "ph": "B", "ts": 1, "pid": 1, "tid": 1},
+            {"name": "inner", "ph": "B", "ts": 2, "pid": 1, "tid": 1},
+            {"name": "outer", "ph": "E", "ts": 3, "pid": 1, "tid": 1},
+            {"name": "inner", "ph": "E", "ts": 1.5, "pid": 1, "tid": 1},
+            {"name": "inner", "ph": "E", "ts": 4, "pid": 1, "tid": 1},
+            {"ph": "E", "ts": 5, "pid": 1, "tid": 1}
+        ]"#;
+
+        match parse_chrome_trace(json.as_bytes(), "trace.json", false) {
+            Err(e) => assert_eq!(e.to_string(), "trace.json: malformed trace event 2, name: ends outer but the open begin is inner"),
+            Ok(_) => unreachable!(),
+        }
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", true).unwrap();
+        assert_eq!(
+            telemetry
+                .zones
+                .iter()
+                .map(|z| (telemetry.names.name(z.name), z.start_time, z.end_time))
+                .collect::<Vec<(&str, u64, u64)>>(),
+            vec![("inner", 2000, 4000), ("outer", 1000, 5000)]
+        );
+        assert_eq!(telemetry.skipped_rows, 2);
+    }
+
+    #[test]
+    fn test_malformed_fields() {
+        let json = r#"[
+            {"name": "a", "ph": "X", "ts": 1, "dur": 2, "pid": 1, "tid": 1},
+            {"name": "no phase", "ts": 1, "pid": 1, "tid": 1},
+            {"name": 7, "ph": "X", "ts": 1, "pid": 1, "tid": 1},
+            {"name": "no ts", "ph": "X", "dur": 1, "pid": 1, "tid": 1},
+            {"name": "negative ts", "ph": "B", "ts": -3, "pid": 1, "tid": 1},
+            {"name": "string dur", "ph": "X", "ts": 1, "dur": "2", "pid": 1, "tid": 1},
+            "not an event",
+            {"name": "instant", "ph": "i", "pid": 1, "tid": 1}
+        ]"#;
+
+        match parse_chrome_trace(json.as_bytes(), "trace.json", false) {
+            Err(e) => assert_eq!(e.to_string(), "trace.json: malformed trace event 1, ph: missing"),
+            Ok(_) => unreachable!(),
+        }
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", true).unwrap();
+        assert_eq!(telemetry.zones.len(), 1);
+        assert_eq!(telemetry.skipped_rows, 6);
+    }
+
+    #[test]
+    fn test_tracks_only_for_zones() -> Result<(), TimelineError> {
+        let json = r#"[
+            {"name": "thread_name", "ph": "M", "pid": 1, "tid": 1, "args": {"name": "Main"}},
+            {"name": "thread_name", "ph": "M", "pid": 1, "tid": 2, "args": {"name": "Idle"}},
+            {"name": "mark", "ph": "i", "ts": 1, "pid": 1, "tid": 3},
+            {"name": "NeverEnds", "ph": "B", "ts": 1, "pid": 1, "tid": 4},
+            {"name": "a", "ph": "X", "ts": 1, "dur": 2, "pid": 1, "tid": 1}
+        ]"#;
+
+        let telemetry = parse_chrome_trace(json.as_bytes(), "trace.json", false)?;
+        assert_eq!(
+            telemetry.tracks.iter().map(|t| (t.name.as_str(), t.id)).collect::<Vec<(&str, usize)>>(),
+            vec![("Main", 2)]
+        );
+        assert_eq!(telemetry.zones[0].track_id, 2);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_invalid_trace_names_the_file() {
+        for (json, reason) in [
+            ("[{", "EOF while parsing an object at line 1 column 2"),
+            (r#"{"events": []}"#, "no traceEvents"),
+            ("3", "expected an array of events or an object with traceEvents"),
+        ] {
+            match parse_chrome_trace(json.as_bytes(), "trace.json", true) {
+                Err(e) => assert_eq!(e.to_string(), format!("trace.json is not a Chrome trace: {}", reason)),
+                Ok(_) => unreachable!(),
+            }
+        }
+    }
+}
@@ -1,16 +1,135 @@
-use std::num::ParseIntError;
+use std::{fmt::Display, num::ParseIntError};
 
 use thiserror::Error;
 
+/// What is wrong with a single row, before it is known where the row came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}: {}", self.field, self.reason);
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TimelineError {
-    #[error("Unable to parse a record")]
+    #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Unable to parse number...")]
+    #[error("Unable to read {file}: {source}")]
+    ReadFile {
+        file: String,
+        source: std::io::Error,
+    },
+
+    #[error("Unable to write {file}: {source}")]
+    WriteFile {
+        file: String,
+        source: std::io::Error,
+    },
+
+    #[error("Unable to parse number: {0}")]
     ParseIntError(#[from] ParseIntError),
 
-    #[error("Unable to parse the csv...")]
+    #[error("Unable to parse the csv: {0}")]
     CsvError(#[from] csv::Error),
+
+    #[error("Unable to parse the json: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// `line` and `record` are both 1 based, `record` only counts csv records.
+    #[error("{file}:{line}: malformed record {record}, {error}")]
+    MalformedRow {
+        file: String,
+        line: u64,
+        record: u64,
+        error: RowError,
+    },
+
+    #[error("{file} is not a Chrome trace: {reason}")]
+    InvalidTrace {
+        file: String,
+        reason: String,
+    },
+
+    /// `event` is the 0 based position of the event in the trace.
+    #[error("{file}: malformed trace event {event}, {error}")]
+    MalformedEvent {
+        file: String,
+        event: usize,
+        error: RowError,
+    },
+
+    #[error("Invalid query file {file}: {source}")]
+    InvalidQueryFile {
+        file: String,
+        source: serde_json::Error,
+    },
+
+    #[error("{query} query: no zone matches {node}")]
+    MissingQueryNode {
+        query: &'static str,
+        node: String,
+    },
+
+    #[error("{query} query: ignore_count is {ignore_count}, but only {found} zones match {node}")]
+    MissingQueryInstance {
+        query: &'static str,
+        node: String,
+        ignore_count: usize,
+        found: usize,
+    },
+
+    #[error("{query} query: only SelfTime, Stat and Cost results have samples a diff can compare")]
+    NotComparable { query: &'static str },
+
+    #[error("{query} query: neither capture has samples to compare")]
+    NothingToCompare { query: &'static str },
+
+    #[error("{0} measurements regressed against the baseline")]
+    Regressions(usize),
+
+    #[error("{failed} of {total} budgets were not met")]
+    OverBudget {
+        failed: usize,
+        total: usize,
+    },
+
+    #[error("{failed} of {total} queries failed")]
+    QueriesFailed {
+        failed: usize,
+        total: usize,
+    },
+
+    #[error("No track matches \"{0}\"")]
+    UnknownTrack(String),
+
+    #[error("Invalid cache: {0}")]
+    InvalidCache(String),
+
+    #[error("Marker \"{name}\" occurrence {occurrence} not found, the capture only has {found}")]
+    MissingMarker {
+        name: String,
+        occurrence: usize,
+        found: usize,
+    },
+
+    #[error("Window starts at {from} after it ends at {to}")]
+    InvertedWindow {
+        from: u64,
+        to: u64,
+    },
+
+    #[error("{dropped} of the {total} instances of {node} were dropped, more than the allowed ratio of {max_ratio}")]
+    DropRatioExceeded {
+        node: String,
+        dropped: usize,
+        total: usize,
+        max_ratio: f64,
+    },
 }
 
@@ -0,0 +1,276 @@
+use std::{fmt::Display, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TimelineError, stats};
+
+/// How bucket edges are laid out between the smallest and largest sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scale {
+    /// `bins` buckets of the same width.
+    Linear,
+    /// `bins` buckets whose edges grow geometrically, for long tailed durations.
+    Log,
+    /// Every power of two split into `bins` buckets of the same width, the way HdrHistogram
+    /// buckets values, so the relative precision is the same at every magnitude.
+    Hdr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    pub low: f64,
+    pub high: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    pub scale: Scale,
+    pub samples: usize,
+    pub trimmed: usize,
+    pub buckets: Vec<Bucket>,
+}
+
+/// Drops every sample more than `deviations` standard deviations above the mean, the same cut
+/// `REDUCE_DATA_BY_STD` made in the old analysis script.  Returns how many samples were dropped.
+pub fn trim_outliers(samples: &mut Vec<u64>, deviations: f64) -> usize {
+    let limit = stats::mean(samples) + stats::stddev(samples) * deviations;
+    let before = samples.len();
+    samples.retain(|s| *s as f64 <= limit);
+
+    return before - samples.len();
+}
+
+fn edges(scale: Scale, bins: usize, min: u64, max: u64) -> Vec<f64> {
+    let bins = bins.max(1);
+    let (min, max) = (min as f64, max as f64);
+
+    // every sample is the same, a single bucket holds them all.
+    if max <= min && scale != Scale::Hdr {
+        return vec![min, min + 1.0];
+    }
+
+    return match scale {
+        Scale::Linear => (0..=bins)
+            .map(|i| min + (max - min) * i as f64 / bins as f64)
+            .collect(),
+        Scale::Log => {
+            let (low, high) = (min.max(1.0).ln(), max.max(1.0).ln());
+            (0..=bins)
+                .map(|i| (low + (high - low) * i as f64 / bins as f64).exp())
+                .collect()
+        }
+        Scale::Hdr => {
+            let mut edges = vec![0.0, 1.0];
+            let mut magnitude = 1.0;
+            while *edges.last().unwrap() <= max {
+                let width = (magnitude / bins as f64).max(1.0);
+                let mut edge = magnitude;
+                while edge < magnitude * 2.0 {
+                    if edge > *edges.last().unwrap() {
+                        edges.push(edge);
+                    }
+                    edge += width;
+                }
+                magnitude *= 2.0;
+            }
+            if *edges.last().unwrap() <= max {
+                edges.push(magnitude);
+            }
+
+            // only keep the edges around the samples.
+            let first = edges.partition_point(|e| *e <= min).saturating_sub(1);
+            let last = edges.partition_point(|e| *e <= max).min(edges.len() - 1);
+            edges[first..=last].to_vec()
+        }
+    };
+}
+
+impl Histogram {
+    pub fn new(samples: &[u64], scale: Scale, bins: usize, trimmed: usize) -> Histogram {
+        let min = samples.iter().min().copied().unwrap_or(0);
+        let max = samples.iter().max().copied().unwrap_or(0);
+        let edges = edges(scale, bins, min, max);
+
+        let mut buckets = edges
+            .windows(2)
+            .map(|w| Bucket {
+                low: w[0],
+                high: w[1],
+                count: 0,
+            })
+            .collect::<Vec<Bucket>>();
+
+        // the last bucket includes its high edge, values outside of the edges are clamped.
+        for sample in samples {
+            let idx = edges[1..].partition_point(|e| *e <= *sample as f64);
+            buckets[idx.min(edges.len() - 2)].count += 1;
+        }
+
+        return Histogram {
+            scale,
+            samples: samples.len(),
+            trimmed,
+            buckets,
+        };
+    }
+
+    /// Draws the histogram as a bar chart, `title` is placed above the bars.
+    pub fn write_svg<W: Write>(&self, title: &str, mut out: W) -> Result<(), TimelineError> {
+        const WIDTH: f64 = 800.0;
+        const HEIGHT: f64 = 400.0;
+        const MARGIN: f64 = 40.0;
+
+        let tallest = self.buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1) as f64;
+        let bar_width = (WIDTH - MARGIN * 2.0) / self.buckets.len().max(1) as f64;
+        let plot_height = HEIGHT - MARGIN * 2.0;
+
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+            WIDTH, HEIGHT
+        )?;
+        writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-size="16">{}</text>"#,
+            WIDTH / 2.0,
+            MARGIN / 2.0,
+            escape_xml(title)
+        )?;
+
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            let height = plot_height * bucket.count as f64 / tallest;
+            writeln!(
+                out,
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="steelblue"><title>{:.0} - {:.0}: {}</title></rect>"#,
+                MARGIN + bar_width * i as f64,
+                HEIGHT - MARGIN - height,
+                bar_width,
+                height,
+                bucket.low,
+                bucket.high,
+                bucket.count
+            )?;
+        }
+
+        let low = self.buckets.first().map(|b| b.low).unwrap_or(0.0);
+        let high = self.buckets.last().map(|b| b.high).unwrap_or(0.0);
+        writeln!(
+            out,
+            r#"<line x1="{m}" y1="{y}" x2="{x}" y2="{y}" stroke="black"/>"#,
+            m = MARGIN,
+            x = WIDTH - MARGIN,
+            y = HEIGHT - MARGIN
+        )?;
+        writeln!(out, r#"<text x="{}" y="{}">{:.0}</text>"#, MARGIN, HEIGHT - MARGIN / 2.0, low)?;
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="end">{:.0}</text>"#,
+            WIDTH - MARGIN,
+            HEIGHT - MARGIN / 2.0,
+            high
+        )?;
+        writeln!(out, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, MARGIN - 4.0, MARGIN, tallest)?;
+        writeln!(out, "</svg>")?;
+        out.flush()?;
+
+        return Ok(());
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    return s
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+const BAR_WIDTH: usize = 50;
+
+/// One line per bucket with its range, a bar scaled to the fullest bucket and its count.
+impl Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tallest = self.buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            let bar = bucket.count * BAR_WIDTH / tallest;
+            let bar = if bar == 0 && bucket.count > 0 { 1 } else { bar };
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{:>14.0} - {:<14.0} |{:<width$} {}",
+                bucket.low,
+                bucket.high,
+                "#".repeat(bar),
+                bucket.count,
+                width = BAR_WIDTH
+            )?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_linear_histogram() {
+        let histogram = Histogram::new(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 10], Scale::Linear, 5, 0);
+
+        assert_eq!(
+            histogram.buckets.iter().map(|b| (b.low, b.high, b.count)).collect::<Vec<(f64, f64, usize)>>(),
+            vec![(0.0, 2.0, 2), (2.0, 4.0, 2), (4.0, 6.0, 2), (6.0, 8.0, 2), (8.0, 10.0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_log_histogram() {
+        let histogram = Histogram::new(&[1, 5, 10, 50, 100, 1000], Scale::Log, 3, 0);
+
+        assert_eq!(
+            histogram.buckets.iter().map(|b| b.count).collect::<Vec<usize>>(),
+            vec![2, 2, 2]
+        );
+        assert!((histogram.buckets[1].low - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hdr_histogram() {
+        let histogram = Histogram::new(&[5, 6, 7, 100], Scale::Hdr, 2, 0);
+
+        // 4..8 is split in 4..6 and 6..8, 64..128 in 64..96 and 96..128.
+        let buckets = histogram.buckets.iter().map(|b| (b.low, b.high, b.count)).collect::<Vec<(f64, f64, usize)>>();
+        assert_eq!(buckets.first(), Some(&(4.0, 6.0, 1)));
+        assert_eq!(buckets.get(1), Some(&(6.0, 8.0, 2)));
+        assert_eq!(buckets.last(), Some(&(96.0, 128.0, 1)));
+        assert_eq!(buckets.iter().map(|b| b.2).sum::<usize>(), 4);
+    }
+
+    #[test]
+    fn test_trim_outliers() {
+        let mut samples = vec![10, 10, 10, 10, 10, 10, 10, 10, 10, 1000];
+
+        assert_eq!(trim_outliers(&mut samples, 1.0), 1);
+        assert_eq!(samples.len(), 9);
+    }
+
+    #[test]
+    fn test_svg() -> Result<(), TimelineError> {
+        let mut out = vec![];
+        Histogram::new(&[1, 2, 3], Scale::Linear, 3, 0).write_svg("a < b", &mut out)?;
+        let svg = String::from_utf8(out).unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("a &lt; b"));
+        assert_eq!(svg.matches("fill=\"steelblue\"").count(), 3);
+
+        return Ok(());
+    }
+}
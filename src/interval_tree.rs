@@ -0,0 +1,186 @@
+use std::ops::Range;
+
+/// A static interval index over intervals sorted by their start.  It is a segment tree over the
+/// sorted positions where each node knows the smallest and largest end beneath it, so a query
+/// only descends into nodes that can still hold an answer.
+///
+/// Every query returns positions into the original sorted input.
+#[derive(Debug, Default)]
+pub struct IntervalTree {
+    starts: Vec<u64>,
+    ends: Vec<u64>,
+    min_end: Vec<u64>,
+    max_end: Vec<u64>,
+}
+
+impl IntervalTree {
+    pub fn new(intervals: &[(u64, u64)]) -> IntervalTree {
+        debug_assert!(intervals.windows(2).all(|w| w[0].0 <= w[1].0), "intervals must be sorted by start");
+
+        let len = intervals.len();
+        let mut tree = IntervalTree {
+            starts: intervals.iter().map(|i| i.0).collect(),
+            ends: intervals.iter().map(|i| i.1).collect(),
+            min_end: vec![u64::MAX; len.max(1) * 4],
+            max_end: vec![0; len.max(1) * 4],
+        };
+
+        if len > 0 {
+            tree.build(1, 0..len);
+        }
+
+        return tree;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.starts.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.starts.is_empty();
+    }
+
+    fn build(&mut self, node: usize, range: Range<usize>) {
+        if range.len() == 1 {
+            self.min_end[node] = self.ends[range.start];
+            self.max_end[node] = self.ends[range.start];
+            return;
+        }
+
+        let mid = range.start + range.len() / 2;
+        self.build(node * 2, range.start..mid);
+        self.build(node * 2 + 1, mid..range.end);
+
+        self.min_end[node] = self.min_end[node * 2].min(self.min_end[node * 2 + 1]);
+        self.max_end[node] = self.max_end[node * 2].max(self.max_end[node * 2 + 1]);
+    }
+
+    /// First position whose start is >= start.
+    fn lower_bound(&self, start: u64) -> usize {
+        return self.starts.partition_point(|s| *s < start);
+    }
+
+    /// First position whose start is > start.
+    fn upper_bound(&self, start: u64) -> usize {
+        return self.starts.partition_point(|s| *s <= start);
+    }
+
+    /// Collects every position within `positions` whose end lies in [min, max].
+    fn collect(&self, positions: Range<usize>, min: u64, max: u64, out: &mut Vec<usize>) {
+        if positions.is_empty() || min > max || self.is_empty() {
+            return;
+        }
+        self.collect_node(1, 0..self.len(), &positions, min, max, out);
+    }
+
+    fn collect_node(
+        &self,
+        node: usize,
+        range: Range<usize>,
+        positions: &Range<usize>,
+        min: u64,
+        max: u64,
+        out: &mut Vec<usize>,
+    ) {
+        if range.end <= positions.start || range.start >= positions.end {
+            return;
+        }
+
+        if self.max_end[node] < min || self.min_end[node] > max {
+            return;
+        }
+
+        if range.len() == 1 {
+            out.push(range.start);
+            return;
+        }
+
+        let mid = range.start + range.len() / 2;
+        self.collect_node(node * 2, range.start..mid, positions, min, max, out);
+        self.collect_node(node * 2 + 1, mid..range.end, positions, min, max, out);
+    }
+
+    /// Every interval that lies within [start, end].
+    pub fn contained(&self, start: u64, end: u64) -> Vec<usize> {
+        let mut out = vec![];
+        self.collect(self.lower_bound(start)..self.upper_bound(end), 0, end, &mut out);
+        return out;
+    }
+
+    /// Every interval that covers [start, end].
+    pub fn containing(&self, start: u64, end: u64) -> Vec<usize> {
+        let mut out = vec![];
+        self.collect(0..self.upper_bound(start), end, u64::MAX, &mut out);
+        return out;
+    }
+
+    /// Every interval that crosses exactly one of the boundaries of [start, end].  This matches
+    /// `Zone::partial_contains`, touching a boundary counts as crossing it.
+    pub fn partially_overlapping(&self, start: u64, end: u64) -> Vec<usize> {
+        let mut out = vec![];
+
+        // starts before, ends inside
+        self.collect(0..self.lower_bound(start), start, end, &mut out);
+
+        // starts inside, ends after
+        if end < u64::MAX {
+            self.collect(self.lower_bound(start)..self.upper_bound(end), end + 1, u64::MAX, &mut out);
+        }
+
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive(intervals: &[(u64, u64)], f: impl Fn(&(u64, u64)) -> bool) -> Vec<usize> {
+        return intervals
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| f(i))
+            .map(|(idx, _)| idx)
+            .collect();
+    }
+
+    #[test]
+    fn test_interval_queries_match_naive_scan() {
+        let mut intervals = vec![];
+        let mut seed = 7u64;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let start = (seed >> 33) % 1000;
+            let len = (seed >> 17) % 100;
+            intervals.push((start, start + len));
+        }
+        intervals.sort();
+
+        let tree = IntervalTree::new(&intervals);
+
+        for &(s, e) in intervals.iter().step_by(7) {
+            let mut contained = tree.contained(s, e);
+            contained.sort();
+            assert_eq!(contained, naive(&intervals, |i| s <= i.0 && i.1 <= e));
+
+            let mut containing = tree.containing(s, e);
+            containing.sort();
+            assert_eq!(containing, naive(&intervals, |i| i.0 <= s && e <= i.1));
+
+            let mut partial = tree.partially_overlapping(s, e);
+            partial.sort();
+            assert_eq!(
+                partial,
+                naive(&intervals, |i| i.0 < s && s <= i.1 && i.1 <= e || s <= i.0 && i.0 <= e && e < i.1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = IntervalTree::new(&[]);
+        assert_eq!(tree.contained(0, 10), Vec::<usize>::new());
+        assert_eq!(tree.containing(0, 10), Vec::<usize>::new());
+        assert_eq!(tree.partially_overlapping(0, 10), Vec::<usize>::new());
+    }
+}
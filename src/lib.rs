@@ -1,14 +1,19 @@
-#![feature(buf_read_has_data_left)]
-#![feature(int_abs_diff)]
-
-#[macro_use] extern crate pretty_assertions;
-
 pub mod tests;
 pub mod opts;
 pub mod zone_search;
+pub mod interval_tree;
+pub mod call_tree;
 pub mod zones;
+pub mod symbols;
 pub mod tracks;
 pub mod error;
 pub mod parse;
+pub mod cache;
+pub mod chrome;
 pub mod query;
+pub mod output;
+pub mod stats;
+pub mod histogram;
+pub mod pattern;
+pub mod capture;
 
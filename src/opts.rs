@@ -1,24 +1,30 @@
 use structopt::StructOpt;
 
+use crate::{output::OutputFormat, parse::Format, tracks::TrackSelection};
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct TimelineOpts {
-    /// Tracks file.  These should contain only TM_TRACKS lines from tms_to_csv output
-    /// A simple cat my_tele_.csv | grep "TM_TRACS" > my_tele.tracks.csv should do it
-    #[structopt(short = "t", long = "track-file")]
-    pub track_file: String,
+    /// Telemetry file.  Either the raw tms_to_csv output, where TM_TRACK and TM_ZONE lines are
+    /// picked out and every other line is ignored, or a Chrome Trace Event Format json file where
+    /// every pid/tid pair is a track.
+    #[structopt(short = "f", long = "file")]
+    pub file: String,
 
-    /// The track you wish to search on.
-    #[structopt(short = "m", long = "main-track", default_value = "Main Thread")]
-    pub main_track: String,
+    /// The track you wish to search on.  Defaults to "Main Thread" for tms_to_csv captures that
+    /// have one, Chrome traces name their tracks after their threads and keep all of them unless a
+    /// track is given.  A track given here has to exist.
+    #[structopt(short = "m", long = "main-track")]
+    pub main_track: Option<String>,
 
-    /// The track you wish to search on.
-    #[structopt(short = "c", long = "context-track", default_value = "Instrumentation")]
-    pub context_track: String,
+    /// The track that provides context (instrumentation zones) for the main track.  Defaults to
+    /// "Instrumentation" for tms_to_csv captures that have one.  A track given here has to exist.
+    #[structopt(short = "c", long = "context-track")]
+    pub context_track: Option<String>,
 
-    /// Zones file.  These should contain only TM_ZONES lines from tms_to_csv output
-    /// A simple cat my_tele_.csv | grep "TM_ZONE" > my_tele.zones.csv should do it
-    #[structopt(short = "z", long = "zone-file")]
-    pub zone_file: String,
+    /// Any additional tracks to search on.  Can be given multiple times and accepts globs,
+    /// `--track "Worker*"` selects every track whose name starts with Worker.
+    #[structopt(short = "t", long = "track")]
+    pub tracks: Vec<String>,
 
     /// Specifies the queries to run over the data provided.
     ///
@@ -53,6 +59,110 @@ pub struct TimelineOpts {
     ///
     /// This assumes that A subsumes B.
     #[structopt(short = "q", long = "query-file")]
-    pub query_file: String,
+    pub query_file: Option<String>,
+
+    /// How query results are printed: text, json, jsonl or csv.  Text is the comma joined line
+    /// per result, the others tag every result with the query that produced it.
+    #[structopt(long = "output-format", default_value = "text")]
+    pub output_format: OutputFormat,
+
+    /// Skip malformed TM_TRACK and TM_ZONE rows, or trace events, instead of stopping at the first
+    /// one.  How many were skipped is printed to stderr.
+    #[structopt(long = "lenient")]
+    pub lenient: bool,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub enum Command {
+    /// Parses the telemetry file once and writes a binary cache of its tracks and sorted zones.
+    /// Later runs against the same file load the cache instead of parsing the csv, for as long as
+    /// the file stays unchanged.
+    Index {
+        /// Where to write the cache.  Defaults to the telemetry file with `.timeline` appended,
+        /// which is where queries look for it.
+        #[structopt(short = "o", long = "output")]
+        output: Option<String>,
+    },
+
+    /// Runs the queries on a baseline capture as well as on the telemetry file, and prints how
+    /// the count, mean, median and p95 of every zone name changed from the baseline.  Every
+    /// change is tested with a Mann-Whitney U test and a bootstrap interval of the median
+    /// difference, and the run fails when any zone regressed.  Only SelfTime, Stat and Cost
+    /// results can be compared, any other query fails without running.
+    Diff {
+        /// The capture to compare against, in any format the telemetry file can be.  The same
+        /// tracks are selected in both.
+        #[structopt(short = "b", long = "baseline")]
+        baseline: String,
+
+        /// Significance level.  A change needs a p value below it, and the median interval is
+        /// taken at a confidence of 1 - alpha.
+        #[structopt(long = "alpha", default_value = "0.05")]
+        alpha: f64,
+
+        /// How many percent the median has to move by before a significant change counts as a
+        /// regression or an improvement.
+        #[structopt(long = "min-change", default_value = "5")]
+        min_change: f64,
+
+        /// Bootstrap resamples the median interval is computed from.
+        #[structopt(long = "resamples", default_value = "1000")]
+        resamples: usize,
+    },
 }
 
+
+impl TimelineOpts {
+    /// The tracks to keep for a capture of the given format, see `select_tracks`.  Every track
+    /// given on the command line is required.  The default main and context tracks are the ones
+    /// tms_to_csv writes and are only kept when the capture has them, a Chrome trace has neither
+    /// so it keeps every track unless some are given.
+    pub fn track_selection(&self, format: Format) -> Vec<TrackSelection> {
+        let defaults: [Option<&str>; 2] = match format {
+            Format::TelemetryCsv => [Some("Main Thread"), Some("Instrumentation")],
+            Format::ChromeTrace => [None, None],
+        };
+
+        return [&self.main_track, &self.context_track]
+            .into_iter()
+            .zip(defaults)
+            .filter_map(|(given, default)| match given {
+                Some(track) => Some(TrackSelection::named(track)),
+                None => default.map(TrackSelection::default_track),
+            })
+            .chain(self.tracks.iter().map(|track| TrackSelection::named(track)))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_track_selection() {
+        let opts = TimelineOpts::from_iter(["timeline", "-f", "capture"]);
+        assert_eq!(
+            opts.track_selection(Format::TelemetryCsv),
+            vec![TrackSelection::default_track("Main Thread"), TrackSelection::default_track("Instrumentation")]
+        );
+        assert_eq!(opts.track_selection(Format::ChromeTrace), vec![]);
+
+        let opts = TimelineOpts::from_iter(["timeline", "-f", "capture", "-m", "CrRendererMain", "-t", "Worker*"]);
+        assert_eq!(
+            opts.track_selection(Format::TelemetryCsv),
+            vec![
+                TrackSelection::named("CrRendererMain"),
+                TrackSelection::default_track("Instrumentation"),
+                TrackSelection::named("Worker*"),
+            ]
+        );
+        assert_eq!(
+            opts.track_selection(Format::ChromeTrace),
+            vec![TrackSelection::named("CrRendererMain"), TrackSelection::named("Worker*")]
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use std::{io::Write, str::FromStr};
+
+use serde::Serialize;
+
+use crate::{
+    error::TimelineError,
+    query::types::{join_diagnostics, Query, QueryResult, Verdict},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The comma joined `Display` of every result, one per line.
+    Text,
+    /// A single json array holding every result.
+    Json,
+    /// One json object per line.
+    Jsonl,
+    /// A csv with a header row, one column for every field any result can have.
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("unknown output format {}, expected text, json, jsonl or csv", s)),
+        };
+    }
+}
+
+/// A result along with the query that produced it.  `query_index` is the position of the query in
+/// the query file.
+#[derive(Debug, Serialize)]
+pub struct TaggedResult<'a> {
+    pub query_index: usize,
+    pub query: &'static str,
+    pub node: Option<&'a str>,
+
+    #[serde(flatten)]
+    pub result: &'a QueryResult,
+}
+
+/// Every result kind flattened into a single row so a csv can hold a mix of them.  Every row has
+/// `query_index`, `query`, `node` and `kind`; the other columns are empty unless `kind` is one of:
+///
+/// - `DataPoint`: name, track, count, additional_data, diagnostics
+/// - `OriginalCsvRow`: row
+/// - `Stat`: name, track, duration, start_time, end_time
+/// - `Cost`: name, track, cost_of_javascript, cost_of_args, cpp_duration, boundary, diagnostics
+/// - `CostSummary`: name, matched, dropped, reason
+/// - `Dropped`: reason, row
+/// - `Aggregate`: name, metric, count, min, max, mean, stddev, median, percentiles
+/// - `Histogram`: name, metric, count, buckets, file
+/// - `Exported`: count, file
+/// - `Diff`: name, metric, count/mean/median/p95 each as _baseline, _candidate, _delta and
+///   _percent, p_value, median_ci_low, median_ci_high, verdict
+#[derive(Debug, Default, Serialize)]
+struct CsvRow<'a> {
+    query_index: usize,
+    query: &'static str,
+    node: Option<&'a str>,
+    kind: &'static str,
+    name: Option<&'a str>,
+    track: Option<&'a str>,
+    /// The DataPoint's value, the number of samples of an Aggregate or Histogram, or the number
+    /// of zones Exported.
+    count: Option<u64>,
+    additional_data: Option<&'a str>,
+    duration: Option<u64>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    cost_of_javascript: Option<u64>,
+    cost_of_args: Option<u64>,
+    cpp_duration: Option<u64>,
+    boundary: Option<&'a str>,
+    matched: Option<usize>,
+    dropped: Option<usize>,
+    /// Why a zone was Dropped, or the drop count of every reason of a CostSummary.
+    reason: Option<String>,
+    diagnostics: Option<String>,
+    metric: Option<&'static str>,
+    min: Option<u64>,
+    max: Option<u64>,
+    mean: Option<f64>,
+    stddev: Option<f64>,
+    median: Option<f64>,
+    percentiles: Option<String>,
+    buckets: Option<String>,
+    /// A Diff's count, mean, median and p95 in the baseline, in the candidate, the candidate minus
+    /// the baseline, and that as a percent of the baseline, empty when the baseline is 0.
+    count_baseline: Option<f64>,
+    count_candidate: Option<f64>,
+    count_delta: Option<f64>,
+    count_percent: Option<f64>,
+    mean_baseline: Option<f64>,
+    mean_candidate: Option<f64>,
+    mean_delta: Option<f64>,
+    mean_percent: Option<f64>,
+    median_baseline: Option<f64>,
+    median_candidate: Option<f64>,
+    median_delta: Option<f64>,
+    median_percent: Option<f64>,
+    p95_baseline: Option<f64>,
+    p95_candidate: Option<f64>,
+    p95_delta: Option<f64>,
+    p95_percent: Option<f64>,
+    p_value: Option<f64>,
+    median_ci_low: Option<f64>,
+    median_ci_high: Option<f64>,
+    verdict: Option<Verdict>,
+    /// The json an Export wrote or the svg of a Histogram.
+    file: Option<&'a str>,
+    /// The original csv row of an OriginalCsvRow or of a Dropped zone.
+    row: Option<&'a str>,
+}
+
+impl<'a> CsvRow<'a> {
+    fn new(tagged: &TaggedResult<'a>) -> CsvRow<'a> {
+        let row = CsvRow {
+            query_index: tagged.query_index,
+            query: tagged.query,
+            node: tagged.node,
+            ..Default::default()
+        };
+
+        return match tagged.result {
+            QueryResult::DataPoint(p) => CsvRow {
+                kind: "DataPoint",
+                name: Some(&p.name),
+                track: Some(&p.track),
+                count: Some(p.count),
+                additional_data: p.additional_data.as_deref(),
+                diagnostics: Some(join_diagnostics(&p.diagnostics)),
+                ..row
+            },
+            QueryResult::OriginalCsvRow(s) => CsvRow {
+                kind: "OriginalCsvRow",
+                row: Some(s),
+                ..row
+            },
+            QueryResult::Stat(s) => CsvRow {
+                kind: "Stat",
+                name: Some(&s.name),
+                track: Some(&s.track),
+                duration: Some(s.duration),
+                start_time: Some(s.start_time),
+                end_time: Some(s.end_time),
+                ..row
+            },
+            QueryResult::Cost(c) => CsvRow {
+                kind: "Cost",
+                name: Some(&c.name),
+                track: Some(&c.track),
+                cost_of_javascript: Some(c.cost_of_javascript),
+                cost_of_args: Some(c.cost_of_args),
+                cpp_duration: Some(c.cpp_duration),
+                boundary: Some(&c.boundary),
+                diagnostics: Some(join_diagnostics(&c.diagnostics)),
+                ..row
+            },
+            QueryResult::CostSummary(s) => CsvRow {
+                kind: "CostSummary",
+                name: Some(&s.node),
+                matched: Some(s.matched),
+                dropped: Some(s.dropped),
+                reason: Some(format!(
+                    "no_boundary={};no_impl_arg={};negative_javascript={}",
+                    s.no_boundary, s.no_impl_arg, s.negative_javascript
+                )),
+                ..row
+            },
+            QueryResult::Dropped(d) => CsvRow {
+                kind: "Dropped",
+                reason: Some(d.reason.to_string()),
+                row: Some(&d.row),
+                ..row
+            },
+            QueryResult::Aggregate(a) => CsvRow {
+                kind: "Aggregate",
+                name: Some(&a.name),
+                metric: Some(a.metric),
+                count: Some(a.summary.count as u64),
+                min: Some(a.summary.min),
+                max: Some(a.summary.max),
+                mean: Some(a.summary.mean),
+                stddev: Some(a.summary.stddev),
+                median: Some(a.summary.median),
+                percentiles: Some(
+                    a.summary
+                        .percentiles
+                        .iter()
+                        .map(|p| format!("p{}={}", p.percentile, p.value))
+                        .collect::<Vec<String>>()
+                        .join(";"),
+                ),
+                ..row
+            },
+            QueryResult::Histogram(h) => CsvRow {
+                kind: "Histogram",
+                name: Some(&h.name),
+                metric: Some(h.metric),
+                file: h.svg.as_deref(),
+                count: Some(h.histogram.samples as u64),
+                buckets: Some(
+                    h.histogram
+                        .buckets
+                        .iter()
+                        .map(|b| format!("{}-{}={}", b.low, b.high, b.count))
+                        .collect::<Vec<String>>()
+                        .join(";"),
+                ),
+                ..row
+            },
+            QueryResult::Exported(e) => CsvRow {
+                kind: "Exported",
+                count: Some(e.zones as u64),
+                file: Some(&e.file),
+                ..row
+            },
+            QueryResult::Diff(d) => CsvRow {
+                kind: "Diff",
+                name: Some(&d.name),
+                metric: Some(d.metric),
+                count_baseline: Some(d.count.baseline),
+                count_candidate: Some(d.count.candidate),
+                count_delta: Some(d.count.delta),
+                count_percent: d.count.percent,
+                mean_baseline: Some(d.mean.baseline),
+                mean_candidate: Some(d.mean.candidate),
+                mean_delta: Some(d.mean.delta),
+                mean_percent: d.mean.percent,
+                median_baseline: Some(d.median.baseline),
+                median_candidate: Some(d.median.candidate),
+                median_delta: Some(d.median.delta),
+                median_percent: d.median.percent,
+                p95_baseline: Some(d.p95.baseline),
+                p95_candidate: Some(d.p95.candidate),
+                p95_delta: Some(d.p95.delta),
+                p95_percent: d.p95.percent,
+                p_value: d.p_value,
+                median_ci_low: d.median_difference.map(|ci| ci.low),
+                median_ci_high: d.median_difference.map(|ci| ci.high),
+                verdict: Some(d.verdict),
+                ..row
+            },
+        };
+    }
+}
+
+enum Sink<W: Write> {
+    Text(W),
+    Json { out: W, written: usize },
+    Jsonl(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+/// Writes the results of every query, as they come, in the chosen format.  `finish` has to be
+/// called once all the queries ran to close out the json array and flush.
+pub struct ResultWriter<W: Write> {
+    sink: Sink<W>,
+}
+
+impl<W: Write> ResultWriter<W> {
+    pub fn new(format: OutputFormat, out: W) -> ResultWriter<W> {
+        let sink = match format {
+            OutputFormat::Text => Sink::Text(out),
+            OutputFormat::Json => Sink::Json { out, written: 0 },
+            OutputFormat::Jsonl => Sink::Jsonl(out),
+            OutputFormat::Csv => Sink::Csv(Box::new(csv::Writer::from_writer(out))),
+        };
+
+        return ResultWriter { sink };
+    }
+
+    pub fn write(&mut self, query_index: usize, query: &Query, results: &[QueryResult]) -> Result<(), TimelineError> {
+        for result in results {
+            let tagged = TaggedResult {
+                query_index,
+                query: query.name(),
+                node: query.node(),
+                result,
+            };
+
+            match &mut self.sink {
+                Sink::Text(out) => writeln!(out, "{}", result)?,
+                Sink::Json { out, written } => {
+                    out.write_all(if *written == 0 { b"[\n" } else { b",\n" })?;
+                    serde_json::to_writer(&mut *out, &tagged)?;
+                    *written += 1;
+                }
+                Sink::Jsonl(out) => {
+                    serde_json::to_writer(&mut *out, &tagged)?;
+                    out.write_all(b"\n")?;
+                }
+                Sink::Csv(out) => out.serialize(CsvRow::new(&tagged))?,
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn finish(self) -> Result<(), TimelineError> {
+        match self.sink {
+            Sink::Text(mut out) | Sink::Jsonl(mut out) => out.flush()?,
+            Sink::Json { mut out, written } => {
+                out.write_all(if written == 0 { b"[]\n" } else { b"\n]\n" })?;
+                out.flush()?;
+            }
+            Sink::Csv(mut out) => out.flush()?,
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        query::{
+            types::{Change, Cost, CostResult, DiffResult, Stat, StatResult},
+            window::Window,
+        },
+        stats::Interval,
+    };
+
+    fn write(format: OutputFormat) -> Result<String, TimelineError> {
+        let cost = Query::Cost(Cost {
+            node: "DataBufferBridge.set".into(),
+            boundaries: None,
+            impl_args: None,
+            emit_dropped: None,
+            max_drop_ratio: None,
+            window: Window::default(),
+        });
+        let stat = Query::Stat(Stat {
+            node: "onMsg, again".into(),
+            window: Window::default(),
+        });
+
+        let mut out = vec![];
+        let mut writer = ResultWriter::new(format, &mut out);
+        writer.write(0, &cost, &[QueryResult::Cost(CostResult {
+            name: "DataBufferBridge.set".to_string(),
+            cpp_duration: 3,
+            cost_of_javascript: 2,
+            cost_of_args: 1,
+            track: "Main Thread".to_string(),
+            boundary: "V8.Builtin_HandleApiCall".to_string(),
+            diagnostics: vec![],
+        })])?;
+        writer.write(1, &stat, &[QueryResult::Stat(StatResult {
+            name: "onMsg, again".to_string(),
+            start_time: 10,
+            end_time: 15,
+            duration: 5,
+            track: "Main Thread".to_string(),
+        })])?;
+        writer.finish()?;
+
+        return Ok(String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_csv_output() -> Result<(), TimelineError> {
+        assert_eq!(
+            write(OutputFormat::Csv)?,
+            "query_index,query,node,kind,name,track,count,additional_data,duration,start_time,end_time,cost_of_javascript,cost_of_args,cpp_duration,boundary,matched,dropped,reason,diagnostics,metric,min,max,mean,stddev,median,percentiles,buckets,count_baseline,count_candidate,count_delta,count_percent,mean_baseline,mean_candidate,mean_delta,mean_percent,median_baseline,median_candidate,median_delta,median_percent,p95_baseline,p95_candidate,p95_delta,p95_percent,p_value,median_ci_low,median_ci_high,verdict,file,row\n\
+             0,Cost,DataBufferBridge.set,Cost,DataBufferBridge.set,Main Thread,,,,,,2,1,3,V8.Builtin_HandleApiCall,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,\n\
+             1,Stat,\"onMsg, again\",Stat,\"onMsg, again\",Main Thread,,,5,10,15,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,,\n"
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_csv_diff_columns() -> Result<(), TimelineError> {
+        let stat = Query::Stat(Stat {
+            node: "onMsg".into(),
+            window: Window::default(),
+        });
+
+        let mut out = vec![];
+        let mut writer = ResultWriter::new(OutputFormat::Csv, &mut out);
+        writer.write(0, &stat, &[QueryResult::Diff(DiffResult {
+            name: "onMsg".to_string(),
+            metric: "duration",
+            count: Change::new(10.0, 12.0),
+            mean: Change::new(100.0, 150.0),
+            median: Change::new(0.0, 5.0),
+            p95: Change::new(200.0, 100.0),
+            p_value: Some(0.01),
+            median_difference: Some(Interval { low: 1.0, high: 9.0 }),
+            verdict: Verdict::Regressed,
+        })])?;
+        writer.finish()?;
+
+        let mut reader = csv::Reader::from_reader(out.as_slice());
+        let headers = reader.headers()?.clone();
+        let record = reader.records().next().unwrap()?;
+        let column = |name: &str| &record[headers.iter().position(|h| h == name).unwrap()];
+
+        assert_eq!(column("kind"), "Diff");
+        assert_eq!(
+            ["count_baseline", "count_candidate", "count_delta", "count_percent"].map(column),
+            ["10.0", "12.0", "2.0", "20.0"]
+        );
+        assert_eq!(column("mean_percent"), "50.0");
+        assert_eq!(column("median_delta"), "5.0");
+        assert_eq!(column("median_percent"), "");
+        assert_eq!(column("p95_delta"), "-100.0");
+        assert_eq!(column("verdict"), "regressed");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_jsonl_output() -> Result<(), TimelineError> {
+        let out = write(OutputFormat::Jsonl)?;
+        let lines = out.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"query_index":0,"query":"Cost","node":"DataBufferBridge.set","kind":"Cost","result":{"name":"DataBufferBridge.set","cpp_duration":3,"cost_of_javascript":2,"cost_of_args":1,"track":"Main Thread","boundary":"V8.Builtin_HandleApiCall","diagnostics":[]}}"#
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_json_output() -> Result<(), TimelineError> {
+        let out: serde_json::Value = serde_json::from_str(&write(OutputFormat::Json)?)?;
+
+        assert_eq!(out.as_array().unwrap().len(), 2);
+        assert_eq!(out[1]["node"], "onMsg, again");
+        assert_eq!(out[1]["result"]["duration"], 5);
+
+        return Ok(());
+    }
+}
@@ -1,34 +1,340 @@
-use std::fs::File;
+use std::{collections::HashSet, fmt::{Debug, Display}, fs::File, io::{BufReader, Read, Seek}, str::FromStr, sync::Mutex};
 
-use log::info;
+use csv::{Position, StringRecord};
+use itertools::Itertools;
+use log::{debug, info, warn};
 
-use crate::{opts::TimelineOpts, error::TimelineError, tracks::Track, zones::Zone};
+use crate::{cache::load_cache_file, chrome::{parse_chrome_trace, ChromeRows}, opts::TimelineOpts, error::{RowError, TimelineError}, symbols::Symbols, tracks::{Track, TrackSelection, select_tracks}, zones::Zone};
 
-pub fn parse_tracks(opts: &TimelineOpts) -> Result<Vec<Track>, TimelineError> {
-    info!("about to parse tracks for file {}", opts.track_file);
-    let mut track_reader = csv::Reader::from_reader(File::open(&opts.track_file)?);
+pub const TM_TRACK: &str = "TM_TRACK";
+pub const TM_ZONE: &str = "TM_ZONE";
 
-    let mut tracks: Vec<Track> = vec![];
-    for result in track_reader.records() {
-        tracks.push(result?.try_into()?);
+#[derive(Debug)]
+pub struct Telemetry {
+    pub tracks: Vec<Track>,
+    pub zones: Vec<Zone>,
+
+    /// The table the zone names are interned in.
+    pub names: Symbols,
+
+    /// Malformed rows skipped by a lenient parse.
+    pub skipped_rows: usize,
+}
+
+impl Telemetry {
+    /// Keeps only the selected tracks, and the zones that belong to them.
+    pub fn select(self, selections: &[TrackSelection]) -> Result<Telemetry, TimelineError> {
+        let tracks = select_tracks(&self.tracks, selections)?;
+        let track_ids = tracks.iter().map(|track| track.id).collect::<HashSet<usize>>();
+        let zones = self
+            .zones
+            .into_iter()
+            .filter(|zone| track_ids.contains(&zone.track_id))
+            .collect::<Vec<Zone>>();
+
+        return Ok(Telemetry { tracks, zones, names: self.names, skipped_rows: self.skipped_rows });
     }
+}
 
-    return Ok(tracks);
+/// The field at idx of a row, `name` is what the field is called in errors.
+pub fn field<'a>(record: &'a StringRecord, idx: usize, name: &'static str) -> Result<&'a str, RowError> {
+    return record.get(idx).ok_or_else(|| RowError {
+        field: name,
+        reason: "missing".to_string(),
+    });
 }
 
-pub fn parse_zones(opts: &TimelineOpts, allowed_tracks: &[Track]) -> Result<Vec<Zone>, TimelineError> {
-    info!("about to parse zones for file {}", opts.zone_file);
-    let mut track_reader = csv::Reader::from_reader(File::open(&opts.zone_file)?);
+pub fn number_field<T>(record: &StringRecord, idx: usize, name: &'static str) -> Result<T, RowError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = field(record, idx, name)?;
+    return value.parse().map_err(|e| RowError {
+        field: name,
+        reason: format!("\"{}\" is not a number, {}", value, e),
+    });
+}
 
+/// Opens a file, naming it in the error if it can't be.
+pub fn open_file(file: &str) -> Result<File, TimelineError> {
+    return File::open(file).map_err(|source| TimelineError::ReadFile {
+        file: file.to_string(),
+        source,
+    });
+}
+
+/// Gives back the original csv row of a zone.  Rows are only needed by a few queries, so instead
+/// of holding on to every row the zone keeps its byte offset and the row is read again on demand.
+/// `names` is the table the zone's name is interned in.
+pub trait RowSource: Debug + Send + Sync {
+    fn row(&self, zone: &Zone, names: &Symbols) -> Result<String, TimelineError>;
+}
+
+#[derive(Debug)]
+pub struct CsvRows<R> {
+    reader: Mutex<csv::Reader<R>>,
+}
+
+impl<R: Read + Seek> CsvRows<R> {
+    pub fn new(reader: R) -> CsvRows<R> {
+        return CsvRows {
+            reader: Mutex::new(telemetry_reader(reader)),
+        };
+    }
+}
+
+impl<R: Read + Seek + Debug + Send> RowSource for CsvRows<R> {
+    fn row(&self, zone: &Zone, _names: &Symbols) -> Result<String, TimelineError> {
+        let mut reader = self.reader.lock().expect("a row read should never panic");
+        let mut position = Position::new();
+        position.set_byte(zone.csv_offset);
+        reader.seek(position)?;
+
+        let mut record = StringRecord::new();
+        reader.read_record(&mut record)?;
+
+        return Ok(record.iter().join(","));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    TelemetryCsv,
+    ChromeTrace,
+}
+
+/// Chrome traces are json, so they start with either `{` or `[`.  Anything else is taken to be
+/// tms_to_csv output.
+pub fn detect_format(file: &str) -> Result<Format, TimelineError> {
+    let mut reader = BufReader::new(open_file(file)?);
+    let mut byte = [0u8; 1];
+
+    while reader.read(&mut byte)? == 1 {
+        if !byte[0].is_ascii_whitespace() {
+            return Ok(match byte[0] {
+                b'{' | b'[' => Format::ChromeTrace,
+                _ => Format::TelemetryCsv,
+            });
+        }
+    }
+
+    return Ok(Format::TelemetryCsv);
+}
+
+pub fn open_rows(opts: &TimelineOpts) -> Result<Box<dyn RowSource>, TimelineError> {
+    return Ok(match detect_format(&opts.file)? {
+        Format::TelemetryCsv => Box::new(CsvRows::new(open_file(&opts.file)?)),
+        Format::ChromeTrace => Box::new(ChromeRows),
+    });
+}
+
+fn telemetry_reader<R: Read>(reader: R) -> csv::Reader<R> {
+    return csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+}
+
+pub fn parse_telemetry_file(opts: &TimelineOpts) -> Result<Telemetry, TimelineError> {
+    let selection = opts.track_selection(detect_format(&opts.file)?);
+    if let Some(telemetry) = load_cache_file(&opts.file) {
+        // a cache written by a lenient run would otherwise let a strict run accept malformed rows,
+        // parsing the source again reports the first of them.
+        if telemetry.skipped_rows == 0 || opts.lenient {
+            return telemetry.select(&selection);
+        }
+        info!("cache of {} skipped {} malformed rows, parsing it again", opts.file, telemetry.skipped_rows);
+    }
+
+    return parse_source(&opts.file, opts.lenient)?.select(&selection);
+}
+
+/// Parses every track and zone out of the file, whatever its format.  With `lenient` malformed
+/// csv rows and trace events are skipped and counted instead of failing the parse.
+pub fn parse_source(file: &str, lenient: bool) -> Result<Telemetry, TimelineError> {
+    let format = detect_format(file)?;
+    info!("about to parse {:?} for file {}", format, file);
+
+    let reader = BufReader::new(open_file(file)?);
+    return match format {
+        Format::TelemetryCsv => parse_telemetry(reader, file, lenient),
+        Format::ChromeTrace => parse_chrome_trace(reader, file, lenient),
+    };
+}
+
+/// Reads the raw tms_to_csv output in a single pass.  Every row is dispatched by its first column,
+/// TM_TRACK rows become tracks, TM_ZONE rows become zones, and everything else is skipped.
+/// `source` is only used to say where a malformed row is.
+pub fn parse_telemetry<R: Read>(reader: R, source: &str, lenient: bool) -> Result<Telemetry, TimelineError> {
+    let mut reader = telemetry_reader(reader);
+
+    let mut tracks: Vec<Track> = vec![];
     let mut zones: Vec<Zone> = vec![];
-    for result in track_reader.records() {
-        let result = result?;
-        let id = result[1].parse::<usize>()?;
+    let mut names = Symbols::default();
+    let mut skipped = 0;
+    let mut malformed = 0;
+    let mut record = StringRecord::new();
+
+    while reader.read_record(&mut record)? {
+        let parsed = match record.get(0) {
+            Some(TM_TRACK) => record.clone().try_into().map(|track| tracks.push(track)),
+            Some(TM_ZONE) => Zone::from_record(&record, &mut names).map(|zone| zones.push(zone)),
+            _ => {
+                skipped += 1;
+                Ok(())
+            }
+        };
+
+        if let Err(error) = parsed {
+            let position = record.position().cloned().unwrap_or_else(Position::new);
+            let error = TimelineError::MalformedRow {
+                file: source.to_string(),
+                line: position.line(),
+                record: position.record() + 1,
+                error,
+            };
+
+            if !lenient {
+                return Err(error);
+            }
+            warn!("skipping {}", error);
+            malformed += 1;
+        }
+    }
+
+    debug!("parsed {} tracks, {} zones, skipped {} rows", tracks.len(), zones.len(), skipped);
+
+    // zones can show up before their track does, so the track filter has to wait until the end.
+    let track_ids = tracks.iter().map(|track| track.id).collect::<HashSet<usize>>();
+    zones.retain(|zone| track_ids.contains(&zone.track_id));
+
+    return Ok(Telemetry { tracks, zones, names, skipped_rows: malformed });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_mixed_telemetry() -> Result<(), TimelineError> {
+        let csv = "\
+TM_ZONE,65536,V8.ExternalCallback,1642630333023852127,1642630333023877210,7,0x0,0x0,Telemetry.h,111,0,0
+TM_TRACK,65536,Main Thread,1642630333000000000
+TM_PLOT,65536,memory,1642630333023852127,42
+TM_ZONE,65537,off_track,1642630333023852127,1642630333023877210,7,0x0,0x0,Telemetry.h,111,0,0
+TM_ZONE,65536,toImplArgs2,1642630333023856211,1642630333023858544,9,0x0,0x0,Telemetry.h,111,0,0
+";
+
+        let telemetry = parse_telemetry(csv.as_bytes(), "test.csv", false)?;
+
+        assert_eq!(telemetry.tracks.len(), 1);
+        assert_eq!(telemetry.tracks[0].name, "Main Thread");
+        assert_eq!(
+            telemetry.zones.iter().map(|z| telemetry.names.name(z.name)).collect::<Vec<&str>>(),
+            vec!["V8.ExternalCallback", "toImplArgs2"]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_select_telemetry() -> Result<(), TimelineError> {
+        let csv = "\
+TM_TRACK,1,Main Thread,0
+TM_TRACK,2,Instrumentation,0
+TM_TRACK,3,Worker,0
+TM_ZONE,1,main,10,20,0
+TM_ZONE,2,instrumentation,10,20,0
+TM_ZONE,3,worker,10,20,0
+";
+
+        let telemetry = parse_telemetry(csv.as_bytes(), "test.csv", false)?.select(&[TrackSelection::named("Main Thread"), TrackSelection::named("Worker")])?;
 
-        if allowed_tracks.iter().any(|track| track.id == id) {
-            zones.push(result.try_into()?);
+        assert_eq!(telemetry.tracks.len(), 2);
+        assert_eq!(
+            telemetry.zones.iter().map(|z| telemetry.names.name(z.name)).collect::<Vec<&str>>(),
+            vec!["main", "worker"]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_rows_by_offset() -> Result<(), TimelineError> {
+        let csv = "\
+TM_TRACK,1,Main Thread,0
+TM_ZONE,1,first,10,20,0
+TM_ZONE,1,\"quoted, name\",10,20,0
+";
+
+        let telemetry = parse_telemetry(csv.as_bytes(), "test.csv", false)?;
+        let rows = CsvRows::new(std::io::Cursor::new(csv.as_bytes()));
+
+        assert_eq!(rows.row(&telemetry.zones[1], &telemetry.names)?, "TM_ZONE,1,quoted, name,10,20,0");
+        assert_eq!(rows.row(&telemetry.zones[0], &telemetry.names)?, "TM_ZONE,1,first,10,20,0");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_malformed_row() {
+        let csv = "\
+TM_TRACK,1,Main Thread,0
+TM_ZONE,1,first,10,20,0
+TM_ZONE,1,second,ten,20,0
+";
+
+        match parse_telemetry(csv.as_bytes(), "test.csv", false) {
+            Err(TimelineError::MalformedRow { file, line, error, .. }) => {
+                assert_eq!(file, "test.csv");
+                assert_eq!(line, 3);
+                assert_eq!(error.field, "start time");
+            }
+            other => panic!("expected a malformed row, got {:?}", other),
         }
     }
 
-    return Ok(zones);
+    #[test]
+    fn test_lenient_parse() -> Result<(), TimelineError> {
+        let csv = "\
+TM_TRACK,1,Main Thread,0
+TM_TRACK,x
+TM_ZONE,1,first,10,20,0
+TM_ZONE,1,backwards,20,10,0
+TM_ZONE,1
+";
+
+        let telemetry = parse_telemetry(csv.as_bytes(), "test.csv", true)?;
+
+        assert_eq!(telemetry.skipped_rows, 3);
+        assert_eq!(telemetry.tracks.len(), 1);
+        assert_eq!(
+            telemetry.zones.iter().map(|z| telemetry.names.name(z.name)).collect::<Vec<&str>>(),
+            vec!["first"]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_lenient_cache_in_strict_run() -> Result<(), TimelineError> {
+        use crate::cache::{cache_path, write_cache_file};
+        use structopt::StructOpt;
+
+        let file = std::env::temp_dir().join("timeline_test_lenient_cache.csv").display().to_string();
+        std::fs::write(&file, "TM_TRACK,1,Main Thread,0\nTM_ZONE,1,first,10,20,0\nTM_ZONE,1,second,ten,20,0\n")?;
+        write_cache_file(&parse_source(&file, true)?, &file, &cache_path(&file))?;
+
+        let lenient = TimelineOpts::from_iter(["timeline", "-f", &file, "--lenient"]);
+        assert_eq!(parse_telemetry_file(&lenient)?.skipped_rows, 1);
+
+        let strict = TimelineOpts::from_iter(["timeline", "-f", &file]);
+        assert!(matches!(parse_telemetry_file(&strict), Err(TimelineError::MalformedRow { line: 3, .. })));
+
+        std::fs::remove_file(cache_path(&file))?;
+        std::fs::remove_file(&file)?;
+
+        return Ok(());
+    }
 }
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::tracks::glob_matches;
+
+/// A zone name as written in a query file.  A plain string only matches that exact name, globs
+/// and regexes have to be marked as such: `{"glob": "INST_*_CLASS*"}` or
+/// `{"regex": "^INST_.*_CLASS"}`.  Regexes are compiled when the query file is read.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawPattern")]
+pub enum NamePattern {
+    Exact(String),
+    Glob(String),
+    Regex(Regex),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPattern {
+    Exact(String),
+    Glob { glob: String },
+    Regex { regex: String },
+}
+
+impl TryFrom<RawPattern> for NamePattern {
+    type Error = regex::Error;
+
+    fn try_from(raw: RawPattern) -> Result<Self, Self::Error> {
+        return Ok(match raw {
+            RawPattern::Exact(name) => NamePattern::Exact(name),
+            RawPattern::Glob { glob } => NamePattern::Glob(glob),
+            RawPattern::Regex { regex } => NamePattern::Regex(Regex::new(&regex)?),
+        });
+    }
+}
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        return match self {
+            NamePattern::Exact(exact) => exact == name,
+            NamePattern::Glob(glob) => glob_matches(glob, name),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        };
+    }
+
+    /// The pattern as it was written.
+    pub fn as_str(&self) -> &str {
+        return match self {
+            NamePattern::Exact(exact) => exact,
+            NamePattern::Glob(glob) => glob,
+            NamePattern::Regex(regex) => regex.as_str(),
+        };
+    }
+}
+
+impl From<&str> for NamePattern {
+    fn from(name: &str) -> Self {
+        return NamePattern::Exact(name.to_string());
+    }
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        return match (self, other) {
+            (NamePattern::Exact(a), NamePattern::Exact(b)) => a == b,
+            (NamePattern::Glob(a), NamePattern::Glob(b)) => a == b,
+            (NamePattern::Regex(a), NamePattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        };
+    }
+}
+
+impl Display for NamePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            NamePattern::Exact(exact) => write!(f, "{}", exact),
+            NamePattern::Glob(glob) => write!(f, "glob {}", glob),
+            NamePattern::Regex(regex) => write!(f, "regex {}", regex),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pattern(json: &str) -> NamePattern {
+        return serde_json::from_str(json).unwrap();
+    }
+
+    #[test]
+    fn test_name_patterns() {
+        let exact = pattern(r#""INST_DataBufferBridge_CLASSgetUint8""#);
+        let glob = pattern(r#"{"glob": "INST_*_CLASS*"}"#);
+        let regex = pattern(r#"{"regex": "^INST_.*_CLASS(get|set)"}"#);
+
+        assert!(exact.matches("INST_DataBufferBridge_CLASSgetUint8"));
+        assert!(!exact.matches("INST_DataBufferBridge_CLASSsetUint8"));
+        assert!(glob.matches("INST_DataBufferBridge_CLASSsetUint8"));
+        assert!(!glob.matches("DataBufferBridge.getUint8"));
+        assert!(regex.matches("INST_Foo_CLASSset"));
+        assert!(!regex.matches("INST_Foo_CLASSdelete"));
+        assert_eq!(regex.as_str(), "^INST_.*_CLASS(get|set)");
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(serde_json::from_str::<NamePattern>(r#"{"regex": "("}"#).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+use crate::{pattern::NamePattern, stats};
+
+use super::types::QueryResult;
+
+/// What a budget measures over the samples of a metric.  Written as `count`, `min`, `max`,
+/// `mean`, `median`, `stddev` or a percentile such as `p95` or `p99.9`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Statistic {
+    Count,
+    Min,
+    Max,
+    Mean,
+    Median,
+    Stddev,
+    Percentile(f64),
+}
+
+impl TryFrom<String> for Statistic {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        return Ok(match s.as_str() {
+            "count" => Statistic::Count,
+            "min" => Statistic::Min,
+            "max" => Statistic::Max,
+            "mean" => Statistic::Mean,
+            "median" => Statistic::Median,
+            "stddev" => Statistic::Stddev,
+            _ => match s.strip_prefix('p').and_then(|p| p.parse::<f64>().ok()) {
+                Some(p) if (0.0..=100.0).contains(&p) => Statistic::Percentile(p),
+                _ => {
+                    return Err(format!(
+                        "unknown statistic {}, expected count, min, max, mean, median, stddev or a percentile like p95",
+                        s
+                    ))
+                }
+            },
+        });
+    }
+}
+
+impl Statistic {
+    pub fn of(&self, samples: &[u64]) -> f64 {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        return match self {
+            Statistic::Count => sorted.len() as f64,
+            Statistic::Min => sorted.first().copied().unwrap_or(0) as f64,
+            Statistic::Max => sorted.last().copied().unwrap_or(0) as f64,
+            Statistic::Mean => stats::mean(&sorted),
+            Statistic::Median => stats::percentile(&sorted, 50.0),
+            Statistic::Stddev => stats::stddev(&sorted),
+            Statistic::Percentile(p) => stats::percentile(&sorted, *p),
+        };
+    }
+}
+
+impl Display for Statistic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Statistic::Count => write!(f, "count"),
+            Statistic::Min => write!(f, "min"),
+            Statistic::Max => write!(f, "max"),
+            Statistic::Mean => write!(f, "mean"),
+            Statistic::Median => write!(f, "median"),
+            Statistic::Stddev => write!(f, "stddev"),
+            Statistic::Percentile(p) => write!(f, "p{}", p),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Limit {
+    /// The statistic must not go above `max`, in the unit of the metric, nanoseconds for every
+    /// duration.
+    Max { max: f64 },
+    /// The statistic must not go above `max_share` (0 to 1) of the same statistic taken over the
+    /// totals of the results, the sum of every metric of a result.  Only useful for Cost, whose
+    /// results measure the javascript, the arguments and the c++ of a call.
+    MaxShare { max_share: f64 },
+}
+
+/// A limit on the results of the queries, checked once they all ran.
+///
+/// ```json
+/// {"node": "onRTPPacketReceived", "statistic": "p95", "max": 2000000}
+/// {"node": "DataBufferBridge.set", "metric": "cost_of_args", "statistic": "mean", "max_share": 0.2}
+/// ```
+///
+/// `metric` is one of the metrics `Aggregate` reports, and can be left out when the node's
+/// results only have one.  `query` restricts the budget to the results of the query at that
+/// position of the query file, otherwise the results of every query count.  A `count` with a
+/// `max` over no results at all is 0, the other statistics fail without samples to measure.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Budget {
+    pub node: NamePattern,
+    pub metric: Option<String>,
+    pub statistic: Statistic,
+    pub query: Option<usize>,
+    #[serde(flatten)]
+    pub limit: Limit,
+}
+
+/// A budget along with what was measured for it.  `measured` is why nothing could be measured
+/// when it failed, which also fails the budget.
+#[derive(Debug, PartialEq)]
+pub struct BudgetCheck<'a> {
+    pub budget: &'a Budget,
+    pub metric: Option<&'static str>,
+    pub measured: Result<f64, String>,
+}
+
+impl BudgetCheck<'_> {
+    pub fn passed(&self) -> bool {
+        let limit = match self.budget.limit {
+            Limit::Max { max } => max,
+            Limit::MaxShare { max_share } => max_share,
+        };
+        return self.measured.as_ref().is_ok_and(|measured| *measured <= limit);
+    }
+}
+
+/// `ok|FAIL statistic metric of node: measured (limit)`
+impl Display for BudgetCheck<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let budget = self.budget;
+        write!(
+            f,
+            "{} {} {} of {}: ",
+            if self.passed() { "ok" } else { "FAIL" },
+            budget.statistic,
+            self.metric.or(budget.metric.as_deref()).unwrap_or("?"),
+            budget.node
+        )?;
+
+        return match (&self.measured, budget.limit) {
+            (Err(problem), _) => write!(f, "{}", problem),
+            (Ok(measured), Limit::Max { max }) => write!(f, "{:.2} (max {:.2})", measured, max),
+            (Ok(measured), Limit::MaxShare { max_share }) => write!(
+                f,
+                "{:.2}% of total (max {:.2}%)",
+                measured * 100.0,
+                max_share * 100.0
+            ),
+        };
+    }
+}
+
+fn check<'a>(budget: &'a Budget, results: &[(usize, Vec<QueryResult>)]) -> BudgetCheck<'a> {
+    let results = results
+        .iter()
+        .filter(|(idx, _)| budget.query.map_or(true, |query| query == *idx))
+        .flat_map(|(_, results)| results)
+        .map(|result| result.samples())
+        .filter(|samples| samples.first().is_some_and(|(name, _, _)| budget.node.matches(name)))
+        .collect::<Vec<Vec<(&str, &'static str, u64)>>>();
+
+    let mut metrics = results.iter().flatten().map(|(_, metric, _)| *metric).collect::<Vec<&'static str>>();
+    metrics.sort_unstable();
+    metrics.dedup();
+
+    // nothing matched the node, there is nothing to count, but every other statistic needs samples.
+    if metrics.is_empty() && budget.statistic == Statistic::Count && matches!(budget.limit, Limit::Max { .. }) {
+        return BudgetCheck { budget, metric: None, measured: Ok(0.0) };
+    }
+
+    let metric = match (&budget.metric, metrics.as_slice()) {
+        (_, []) => Err("no results".to_string()),
+        (Some(metric), _) => metrics
+            .iter()
+            .copied()
+            .find(|m| m == metric)
+            .ok_or_else(|| format!("no {} results, only {}", metric, metrics.join(", "))),
+        (None, [metric]) => Ok(*metric),
+        (None, _) => Err(format!("needs a metric, one of {}", metrics.join(", "))),
+    };
+    let metric = match metric {
+        Ok(metric) => metric,
+        Err(problem) => return BudgetCheck { budget, metric: None, measured: Err(problem) },
+    };
+
+    let samples = results
+        .iter()
+        .flatten()
+        .filter(|(_, m, _)| *m == metric)
+        .map(|(_, _, value)| *value)
+        .collect::<Vec<u64>>();
+    let measured = budget.statistic.of(&samples);
+
+    let measured = match budget.limit {
+        Limit::Max { .. } => measured,
+        Limit::MaxShare { .. } => {
+            let totals = results
+                .iter()
+                .map(|samples| samples.iter().fold(0u64, |total, (_, _, value)| total.saturating_add(*value)))
+                .collect::<Vec<u64>>();
+            let total = budget.statistic.of(&totals);
+            if total == 0.0 {
+                return BudgetCheck { budget, metric: Some(metric), measured: Err("total is zero".to_string()) };
+            }
+            measured / total
+        }
+    };
+
+    return BudgetCheck { budget, metric: Some(metric), measured: Ok(measured) };
+}
+
+/// Checks every budget against the results of the queries, given along with the position of the
+/// query that produced them.
+pub fn check_budgets<'a>(budgets: &'a [Budget], results: &[(usize, Vec<QueryResult>)]) -> Vec<BudgetCheck<'a>> {
+    return budgets.iter().map(|budget| check(budget, results)).collect();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query::types::{CostResult, StatResult};
+
+    fn stat(name: &str, duration: u64) -> QueryResult {
+        return QueryResult::Stat(StatResult {
+            name: name.to_string(),
+            start_time: 0,
+            end_time: duration,
+            duration,
+            track: "Main Thread".to_string(),
+        });
+    }
+
+    fn cost(name: &str, javascript: u64, args: u64, cpp: u64) -> QueryResult {
+        return QueryResult::Cost(CostResult {
+            name: name.to_string(),
+            cpp_duration: cpp,
+            cost_of_javascript: javascript,
+            cost_of_args: args,
+            track: "Main Thread".to_string(),
+            boundary: "V8.Builtin_HandleApiCall".to_string(),
+            diagnostics: vec![],
+        });
+    }
+
+    fn budgets(json: &str) -> Vec<Budget> {
+        return serde_json::from_str(json).unwrap();
+    }
+
+    #[test]
+    fn test_budgets() {
+        let results = vec![
+            (0, (1..=100).map(|d| stat("onMsg", d * 1000)).collect::<Vec<QueryResult>>()),
+            (1, vec![cost("set", 60, 30, 10), cost("set", 60, 10, 30)]),
+        ];
+        let budgets = budgets(
+            r#"[
+                {"node": "onMsg", "statistic": "p95", "max": 96000},
+                {"node": "onMsg", "statistic": "max", "max": 99000},
+                {"node": "set", "metric": "cost_of_args", "statistic": "mean", "max_share": 0.2},
+                {"node": "set", "statistic": "mean", "max": 100},
+                {"node": "set", "metric": "cost_of_arg", "statistic": "mean", "max": 100},
+                {"node": "missing", "statistic": "count", "max": 0},
+                {"node": "missing", "statistic": "count", "max": 0, "query": 1},
+                {"node": "missing", "statistic": "max", "max": 0},
+                {"node": "onMsg", "statistic": "count", "max": 10, "query": 1},
+                {"node": {"glob": "on*"}, "statistic": "count", "max": 100, "query": 0}
+            ]"#,
+        );
+
+        let checks = check_budgets(&budgets, &results);
+
+        assert_eq!(
+            checks.iter().map(|c| c.to_string()).collect::<Vec<String>>(),
+            vec![
+                "ok p95 duration of onMsg: 95050.00 (max 96000.00)",
+                "FAIL max duration of onMsg: 100000.00 (max 99000.00)",
+                "ok mean cost_of_args of set: 20.00% of total (max 20.00%)",
+                "FAIL mean ? of set: needs a metric, one of cost_of_args, cost_of_javascript, cpp_duration",
+                "FAIL mean cost_of_arg of set: no cost_of_arg results, only cost_of_args, cost_of_javascript, cpp_duration",
+                "ok count ? of missing: 0.00 (max 0.00)",
+                "ok count ? of missing: 0.00 (max 0.00)",
+                "FAIL max ? of missing: no results",
+                "ok count ? of onMsg: 0.00 (max 10.00)",
+                "ok count duration of glob on*: 100.00 (max 100.00)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_statistic() {
+        assert!(serde_json::from_str::<Budget>(r#"{"node": "x", "statistic": "p101", "max": 1}"#).is_err());
+        assert!(serde_json::from_str::<Budget>(r#"{"node": "x", "statistic": "average", "max": 1}"#).is_err());
+        assert!(serde_json::from_str::<Budget>(r#"{"node": "x", "statistic": "mean"}"#).is_err());
+    }
+}
@@ -1,63 +1,92 @@
-use crate::{zone_search::{filter_by_names_on_idx, get_partial_contained, get_contained, filter_out_contains, sum_zone_indices, filter_by_name_on_idx}, zones::Zone};
+use crate::{zone_search::{filter_by_names_on_idx, get_contained, get_partial_contained, Zones}, zones::Zone, symbols::Symbol};
 
-pub fn calculate_self_time(zone_idx: usize, zones: &Vec<Zone>, partial_ignores: &Vec<String>, ignores: &Vec<String>) -> u64 {
-    let zone = zones.get(zone_idx).unwrap();
-    let partials = filter_by_names_on_idx(
-        zones,
-        &get_partial_contained(zones, zone.idx),
-        &partial_ignores,
-    );
+use super::duration::{CheckedDuration, Diagnostic};
 
-    let contains =
-        filter_by_names_on_idx(zones, &get_contained(zones, zone.idx), &ignores);
+/// How much of zone the zones at idxs cover.  Time under more than one of them, ignores on two
+/// tracks that overlap or an ignore inside a partial ignore, only counts once.
+fn covered(zones: &[Zone], zone: &Zone, idxs: &[usize]) -> CheckedDuration {
+    let mut spans = idxs
+        .iter()
+        .map(|idx| (zones[*idx].start_time.max(zone.start_time), zones[*idx].end_time.min(zone.end_time)))
+        .filter(|(start, end)| start < end)
+        .collect::<Vec<(u64, u64)>>();
+    spans.sort();
 
-    let contains = filter_out_contains(zones, &partials, &contains);
+    let mut merged: Vec<(u64, u64)> = vec![];
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
 
-    // TODO: filter out sub contains within contains
+    return CheckedDuration::sum(merged.into_iter().map(|(start, end)| end - start));
+}
 
-    let partials = sum_zone_indices(zones, &zone, &partials);
-    let contains = sum_zone_indices(zones, &zone, &contains);
+/// `partial_ignores` and `ignores` are the sorted names from `Zones::symbols`.  Both are looked
+/// for on every track, not just the zone's own, so instrumentation zones count as well.
+pub fn calculate_self_time(zone: &Zone, zones: &Zones, partial_ignores: &[Symbol], ignores: &[Symbol]) -> CheckedDuration {
+    let mut removed = filter_by_names_on_idx(zones, &get_partial_contained(zones, zone.idx), partial_ignores);
+    removed.extend(get_ignored(zones, zone.idx, ignores));
 
-    return zone.duration.saturating_sub(partials).saturating_sub(contains);
+    return CheckedDuration::new(zone.duration).minus(covered(zones, zone, &removed), Diagnostic::self_time_underflow);
 }
 
-pub fn get_start_of_cpp(zones: &Vec<Zone>, parents: &Vec<usize>) -> Option<usize> {
-    let mut start_of_cpp_call: Option<usize> = None;
-    {
-        let possible = filter_by_name_on_idx(zones, &parents, "V8.Builtin_HandleApiCall");
-        if possible.is_empty() {
-            let possible = filter_by_name_on_idx(zones, &parents, "V8.ExternalCallback");
-            if !possible.is_empty() {
-                start_of_cpp_call = Some(*possible.get(0).unwrap());
-            }
-        } else {
-            start_of_cpp_call = Some(*possible.get(0).unwrap());
-        }
-    }
+/// Where javascript calls into c++ in V8, tried in this order.
+pub const DEFAULT_BOUNDARIES: [&str; 2] = ["V8.Builtin_HandleApiCall", "V8.ExternalCallback"];
+
+/// Where V8 bindings convert the javascript arguments.  There is also a `toImplArgs`, but it is
+/// barely used so it is not looked for unless a query asks for it.
+pub const DEFAULT_IMPL_ARGS: [&str; 1] = ["toImplArgs2"];
 
-    return start_of_cpp_call;
+/// The nearest parent matching the first of `boundaries` that matches any parent at all.  Every
+/// boundary is a sorted list of names from `Zones::symbols`.
+pub fn get_start_of_cpp(zones: &[Zone], parents: &[usize], boundaries: &[Vec<Symbol>]) -> Option<usize> {
+    return boundaries
+        .iter()
+        .find_map(|names| filter_by_names_on_idx(zones, parents, names).first().copied());
 }
 
-pub fn calculate_total_time(zone: &Zone, zones: &Vec<Zone>, ignores: &Vec<String>) -> u64 {
-    let contains =
-        filter_by_names_on_idx(zones, &get_contained(zones, zone.idx), &ignores);
+/// The outermost ignored zones within the zone at idx, on any track, the same as partial ignores.
+/// They are found walking down the call tree from its direct children, and from the zones on other
+/// tracks that nothing else within it encloses, stopping at every ignored zone.  Anything nested
+/// inside one is already accounted for by it.
+pub fn get_ignored(zones: &Zones, idx: usize, ignores: &[Symbol]) -> Vec<usize> {
+    let zone = &zones[idx];
+    let tree = zones.call_tree();
 
-    // TODO: filter out sub contains within contains
+    let mut stack = get_contained(zones, idx)
+        .into_iter()
+        .filter(|other| zones[*other].track_id != zone.track_id)
+        .filter(|other| tree.parent(*other).map_or(true, |parent| !zone.contains(&zones[parent])))
+        .collect::<Vec<usize>>();
+    stack.extend(tree.children(idx));
 
-    let contains = sum_zone_indices(zones, &zone, &contains);
+    let mut out = vec![];
+    while let Some(curr) = stack.pop() {
+        if ignores.binary_search(&zones[curr].name).is_ok() {
+            out.push(curr);
+        } else {
+            stack.extend(tree.children(curr));
+        }
+    }
+    out.sort();
 
-    return zone.duration.saturating_sub(contains);
+    return out;
 }
 
-pub fn get_impl_arg(zones: &Vec<Zone>, parent: usize) -> Option<usize> {
-    // TODO: toImplArgs?  There are two versions of these, but it doesn't seem to be used very
-    // much.
-    let contains =
-        filter_by_name_on_idx(zones, &get_contained(zones, parent), "toImplArgs2");
+pub fn calculate_total_time(zone: &Zone, zones: &Zones, ignores: &[Symbol]) -> CheckedDuration {
+    let removed = covered(zones, zone, &get_ignored(zones, zone.idx, ignores));
 
-    if contains.is_empty() {
-        return None;
-    }
+    return CheckedDuration::new(zone.duration).minus(removed, Diagnostic::total_time_underflow);
+}
+
+/// The first zone under parent, in call order, matching the first of `impl_args` that matches
+/// anything.
+pub fn get_impl_arg(zones: &Zones, parent: usize, impl_args: &[Vec<Symbol>]) -> Option<usize> {
+    let descendants = zones.call_tree().descendants(parent);
 
-    return Some(*contains.get(0).unwrap());
+    return impl_args
+        .iter()
+        .find_map(|names| filter_by_names_on_idx(zones, &descendants, names).first().copied());
 }
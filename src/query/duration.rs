@@ -0,0 +1,161 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// Something that didn't add up while measuring a zone.  The measurement is still made, clamped
+/// to what fits in a u64, and the diagnostic is attached to the result row it ended up in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Diagnostic {
+    /// The partial and ignored zones removed from a zone add up to more than the zone itself.
+    SelfTimeUnderflow { duration: u64, removed: u64 },
+    /// The ignored zones removed from a zone add up to more than the zone itself.
+    TotalTimeUnderflow { duration: u64, removed: u64 },
+    /// The c++ self time of a Cost node is longer than the time from its boundary, what is left
+    /// of the boundary once ignored zones are removed.
+    JavascriptUnderflow { duration: u64, removed: u64 },
+    /// Nothing under the boundary of a Cost node matched an impl arg, its arguments cost nothing.
+    NoImplArg,
+    /// Adding durations up went past u64::MAX.
+    Overflow,
+}
+
+impl Diagnostic {
+    pub fn self_time_underflow(duration: u64, removed: u64) -> Diagnostic {
+        return Diagnostic::SelfTimeUnderflow { duration, removed };
+    }
+
+    pub fn total_time_underflow(duration: u64, removed: u64) -> Diagnostic {
+        return Diagnostic::TotalTimeUnderflow { duration, removed };
+    }
+
+    pub fn javascript_underflow(duration: u64, removed: u64) -> Diagnostic {
+        return Diagnostic::JavascriptUnderflow { duration, removed };
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Diagnostic::SelfTimeUnderflow { duration, removed } => {
+                write!(f, "self_time_underflow({} from {})", removed, duration)
+            }
+            Diagnostic::TotalTimeUnderflow { duration, removed } => {
+                write!(f, "total_time_underflow({} from {})", removed, duration)
+            }
+            Diagnostic::JavascriptUnderflow { duration, removed } => {
+                write!(f, "javascript_underflow({} from {})", removed, duration)
+            }
+            Diagnostic::NoImplArg => write!(f, "no_impl_arg"),
+            Diagnostic::Overflow => write!(f, "overflow"),
+        };
+    }
+}
+
+/// Nanoseconds that never wrap.  Arithmetic that would go below zero or past u64::MAX clamps and
+/// records a `Diagnostic` instead, and the diagnostics of both operands carry over to the result.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckedDuration {
+    nanos: u64,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckedDuration {
+    pub fn new(nanos: u64) -> CheckedDuration {
+        return CheckedDuration {
+            nanos,
+            diagnostics: vec![],
+        };
+    }
+
+    /// A duration that is already known to be off, see `Diagnostic`.
+    pub fn with_diagnostic(nanos: u64, diagnostic: Diagnostic) -> CheckedDuration {
+        return CheckedDuration {
+            nanos,
+            diagnostics: vec![diagnostic],
+        };
+    }
+
+    pub fn sum(durations: impl Iterator<Item = u64>) -> CheckedDuration {
+        return durations.fold(CheckedDuration::default(), |sum, d| sum.plus(CheckedDuration::new(d)));
+    }
+
+    pub fn nanos(&self) -> u64 {
+        return self.nanos;
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return &self.diagnostics;
+    }
+
+    pub fn plus(mut self, other: CheckedDuration) -> CheckedDuration {
+        self.merge(other.diagnostics);
+        self.nanos = match self.nanos.checked_add(other.nanos) {
+            Some(nanos) => nanos,
+            None => {
+                self.merge(vec![Diagnostic::Overflow]);
+                u64::MAX
+            }
+        };
+
+        return self;
+    }
+
+    /// `self - other`, clamped at zero with `underflow(self, other)` recorded when other is longer.
+    pub fn minus(mut self, other: CheckedDuration, underflow: fn(u64, u64) -> Diagnostic) -> CheckedDuration {
+        let (nanos, diagnostic) = match self.nanos.checked_sub(other.nanos) {
+            Some(nanos) => (nanos, None),
+            None => (0, Some(underflow(self.nanos, other.nanos))),
+        };
+
+        self.merge(other.diagnostics);
+        self.merge(diagnostic.into_iter().collect());
+        self.nanos = nanos;
+
+        return self;
+    }
+
+    /// `self - other`, or none when other is longer.
+    pub fn checked_minus(mut self, other: CheckedDuration) -> Option<CheckedDuration> {
+        self.nanos = self.nanos.checked_sub(other.nanos)?;
+        self.merge(other.diagnostics);
+
+        return Some(self);
+    }
+
+    fn merge(&mut self, diagnostics: Vec<Diagnostic>) {
+        for diagnostic in diagnostics {
+            if !self.diagnostics.contains(&diagnostic) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let removed = CheckedDuration::sum([5, 10].into_iter());
+        assert_eq!(removed.nanos(), 15);
+
+        let fine = CheckedDuration::new(20).minus(removed.clone(), Diagnostic::self_time_underflow);
+        assert_eq!(fine, CheckedDuration::new(5));
+
+        let underflow = CheckedDuration::new(10).minus(removed.clone(), Diagnostic::self_time_underflow);
+        assert_eq!(underflow.nanos(), 0);
+        assert_eq!(underflow.diagnostics(), &[Diagnostic::SelfTimeUnderflow { duration: 10, removed: 15 }]);
+
+        // diagnostics carry over to whatever is computed from the result.
+        let carried = CheckedDuration::new(100).checked_minus(underflow.clone()).unwrap();
+        assert_eq!(carried.nanos(), 100);
+        assert_eq!(carried.diagnostics(), underflow.diagnostics());
+        assert_eq!(CheckedDuration::new(10).checked_minus(removed), None);
+
+        let overflow = CheckedDuration::sum([u64::MAX, 1].into_iter());
+        assert_eq!(overflow.nanos(), u64::MAX);
+        assert_eq!(overflow.diagnostics(), &[Diagnostic::Overflow]);
+    }
+}
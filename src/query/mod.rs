@@ -1,258 +1,720 @@
+pub mod budget;
 pub mod calculations;
-pub mod query;
+pub mod duration;
 pub mod query_config;
+pub mod types;
+pub mod window;
+
+/// The query types used to live in `query::query`, kept under that path for existing callers.
+pub use types as query;
+
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::Path,
+};
 
 use log::{info, debug};
 
 use crate::{
+    chrome::write_chrome_trace,
     error::TimelineError,
+    histogram::{self, trim_outliers, Scale},
+    pattern::NamePattern,
+    parse::RowSource,
+    stats::{bootstrap_median_difference, mann_whitney_u, Interval, Summary, DEFAULT_PERCENTILES},
+    symbols::Symbol,
     zone_search::{
-        filter_by_name, filter_by_names, get_by_name, get_contained, get_parents, get_partial_contained
+        filter_by_name, get_contained, get_partial_contained, Zones,
     },
+    tracks::{track_name, Track},
     zones::Zone,
 };
 
 use self::{
-    calculations::{calculate_self_time, get_start_of_cpp, calculate_total_time, get_impl_arg},
-    query::{Cost, DataPoint, Query, QueryResult, Reduce, SelfTime, Stat, StatResult, CostResult},
+    calculations::{
+        calculate_self_time, calculate_total_time, get_impl_arg, get_start_of_cpp,
+        DEFAULT_BOUNDARIES, DEFAULT_IMPL_ARGS,
+    },
+    duration::{CheckedDuration, Diagnostic},
+    types::{
+        Aggregate, AggregateResult, Change, Cost, CostResult, CostSummary, DataPoint, DiffResult,
+        DiffThreshold, DropReason, DroppedZone, Export, ExportResult, Histogram, HistogramResult, Query,
+        QueryResult, Reduce, SelfTime, Stat, StatResult, Verdict,
+    },
     query_config::QueryConfig,
+    window::Window,
 };
 
-fn index_to_original_csv(zones: &Vec<Zone>, idx: usize) -> QueryResult {
-    return QueryResult::OriginalCsvRow(
-        zones
-            .get(idx)
-            .expect("all indices should be valid")
-            .original_csv
-            .to_string(),
-    );
+fn zone_to_original_csv(zone: &Zone, zones: &Zones, rows: &dyn RowSource) -> Result<QueryResult, TimelineError> {
+    return Ok(QueryResult::OriginalCsvRow(rows.row(zone, zones.names())?));
+}
+
+/// Every zone matching the node of a query.  Finding none is an error, a misspelt node would
+/// otherwise look like a query that ran fine and measured nothing.
+fn find_node(query: &'static str, node: &NamePattern, zones: &Zones) -> Result<Vec<usize>, TimelineError> {
+    let found = filter_by_name(zones, node);
+    if found.is_empty() {
+        return Err(TimelineError::MissingQueryNode {
+            query,
+            node: node.to_string(),
+        });
+    }
+
+    return Ok(found);
 }
 
-fn self_time_query(query: &SelfTime, config: &QueryConfig, zones: &Vec<Zone>) -> Vec<QueryResult> {
-    return get_by_name(zones, query.node.as_str())
+pub(crate) fn self_time_query(query: &SelfTime, config: &QueryConfig, tracks: &[Track], zones: &Zones) -> Result<Vec<QueryResult>, TimelineError> {
+    let partial_ignores = zones.symbols(&query.partial_ignore);
+    let ignores = zones.symbols(&config.ignores);
+
+    return Ok(find_node("SelfTime", &query.node, zones)?
         .iter()
-        .flat_map(|z_idx| {
-            return zones.get(*z_idx);
-        })
+        .filter_map(|z_idx| zones.get(*z_idx))
         .map(|z| {
+            let self_time = calculate_self_time(z, zones, &partial_ignores, &ignores);
             return QueryResult::DataPoint(DataPoint {
                 query: "SelfTime".to_string(),
-                name: z.name.clone(),
-                count: calculate_self_time(z.idx, zones, &query.partial_ignore, &config.ignores),
+                name: zones.name(z).to_string(),
+                count: self_time.nanos(),
                 additional_data: None,
+                track: track_name(tracks, z.track_id),
+                diagnostics: self_time.diagnostics().to_vec(),
             });
         })
-        .collect::<Vec<QueryResult>>();
+        .collect::<Vec<QueryResult>>());
 }
 
-pub fn reduce_query(query: &Reduce, zones: &Vec<Zone>) -> Vec<QueryResult> {
-    let names = vec![query.node.clone()];
-    info!("reduce_query#filte_by_name: {:?}", names);
+/// The zones a reduce is made of.  Every zone partially overlapping the found zone, then every
+/// zone it contains, then the found zone itself.
+fn reduce_indices(
+    query: &'static str,
+    node: &NamePattern,
+    ignore_count: Option<usize>,
+    zones: &Zones,
+) -> Result<Vec<usize>, TimelineError> {
+    info!("reduce_query#filte_by_name: {}", node);
 
-    let found_idxs = filter_by_names(zones, &names);
+    let found_idxs = find_node(query, node, zones)?;
     info!("found indices: {:?}", found_idxs.len());
 
-    let found = *found_idxs
-        .iter()
-        .nth(query.ignore_count.unwrap_or(0))
-        .expect("to always find a reduce result");
+    let ignore_count = ignore_count.unwrap_or(0);
+    let found = found_idxs
+        .get(ignore_count)
+        .and_then(|idx| zones.get(*idx))
+        .ok_or_else(|| TimelineError::MissingQueryInstance {
+            query,
+            node: node.to_string(),
+            ignore_count,
+            found: found_idxs.len(),
+        })?;
 
-    let found = zones.get(found).expect("all indices should be valid");
+    let mut out = get_partial_contained(zones, found.idx);
+    out.append(&mut get_contained(zones, found.idx));
+    out.push(found.idx);
 
-    let partials = get_partial_contained(zones, found.idx);
-    let contains = get_contained(zones, found.idx);
+    return Ok(out);
+}
 
-    let mut out = partials
+pub fn reduce_query(query: &Reduce, zones: &Zones, rows: &dyn RowSource) -> Result<Vec<QueryResult>, TimelineError> {
+    return reduce_indices("Reduce", &query.node, query.ignore_count, zones)?
         .iter()
-        .map(|p| index_to_original_csv(zones, *p))
-        .collect::<Vec<QueryResult>>();
+        .filter_map(|idx| zones.get(*idx))
+        .map(|zone| zone_to_original_csv(zone, zones, rows))
+        .collect::<Result<Vec<QueryResult>, TimelineError>>();
+}
 
-    out.append(
-        &mut contains
-            .iter()
-            .map(|p| index_to_original_csv(zones, *p))
-            .collect::<Vec<QueryResult>>(),
-    );
+pub(crate) fn export_query(export: &Export, tracks: &[Track], zones: &Zones) -> Result<Vec<QueryResult>, TimelineError> {
+    let mut idxs = match &export.node {
+        Some(node) => reduce_indices("Export", node, export.ignore_count, zones)?,
+        None => (0..zones.len()).collect(),
+    };
+    idxs.sort();
 
-    out.push(QueryResult::OriginalCsvRow(found.original_csv.to_string()));
+    info!("exporting {} zones to {}", idxs.len(), export.output);
+    File::create(&export.output)
+        .map_err(TimelineError::from)
+        .and_then(|file| write_chrome_trace(idxs.iter().filter_map(|idx| zones.get(*idx)), zones.names(), tracks, BufWriter::new(file)))
+        .map_err(|e| naming_file(&export.output, e))?;
 
-    return out;
+    return Ok(vec![QueryResult::Exported(ExportResult {
+        file: export.output.to_string(),
+        zones: idxs.len(),
+    })]);
 }
 
-fn stat_query(stat: &Stat, zones: &Vec<Zone>) -> Vec<QueryResult> {
-    return filter_by_names(zones, &vec![stat.node.clone()])
+pub(crate) fn stat_query(stat: &Stat, tracks: &[Track], zones: &Zones) -> Result<Vec<QueryResult>, TimelineError> {
+    return Ok(find_node("Stat", &stat.node, zones)?
         .iter()
-        .map(|z_idx| zones.get(*z_idx).expect("all indices should be valid"))
+        .filter_map(|z_idx| zones.get(*z_idx))
         .map(|z| {
             return QueryResult::Stat(StatResult {
-                name: z.name.clone(),
+                name: zones.name(z).to_string(),
                 duration: z.duration,
                 start_time: z.start_time,
                 end_time: z.end_time,
+                track: track_name(tracks, z.track_id),
             });
         })
-        .collect::<Vec<QueryResult>>();
+        .collect::<Vec<QueryResult>>());
+}
+
+// every pattern of an ordered fallback list resolved on its own, so the order is kept.
+fn fallback_symbols(zones: &Zones, patterns: &Option<Vec<NamePattern>>, defaults: &[&str]) -> Vec<Vec<Symbol>> {
+    return match patterns {
+        Some(patterns) => patterns
+            .iter()
+            .map(|pattern| zones.symbols(std::slice::from_ref(pattern)))
+            .collect(),
+        None => defaults.iter().map(|name| zones.symbols(&[(*name).into()])).collect(),
+    };
+}
+
+struct CostModel {
+    ignores: Vec<Symbol>,
+    boundaries: Vec<Vec<Symbol>>,
+    impl_args: Vec<Vec<Symbol>>,
 }
 
-fn cost_query(cost: &Cost, config: &QueryConfig, zones: &Vec<Zone>) -> Vec<QueryResult> {
+fn cost_of(zone: &Zone, model: &CostModel, tracks: &[Track], zones: &Zones) -> Result<CostResult, DropReason> {
+    let self_time = calculate_self_time(zone, zones, &[], &model.ignores);
+    let parents = zones.call_tree().ancestors(zone.idx);
+
+    // this isn't a proper measurement
+    let start_of_cpp = get_start_of_cpp(zones, &parents, &model.boundaries)
+        .and_then(|idx| zones.get(idx))
+        .ok_or(DropReason::NoBoundary)?;
+
+    let to_cpp_total_time = calculate_total_time(start_of_cpp, zones, &model.ignores);
+
+    let impl_time = match get_impl_arg(zones, start_of_cpp.idx, &model.impl_args).and_then(|idx| zones.get(idx)) {
+        Some(impl_arg) => calculate_self_time(impl_arg, zones, &[], &model.ignores),
+        None => CheckedDuration::with_diagnostic(0, Diagnostic::NoImplArg),
+    };
+
+    let cost_of_javascript = to_cpp_total_time.minus(self_time.clone(), Diagnostic::javascript_underflow);
+
+    let mut diagnostics = cost_of_javascript.diagnostics().to_vec();
+    for diagnostic in impl_time.diagnostics() {
+        if !diagnostics.contains(diagnostic) {
+            diagnostics.push(*diagnostic);
+        }
+    }
+
+    return Ok(CostResult {
+        name: zones.name(zone).to_string(),
+        cost_of_args: impl_time.nanos(),
+        cost_of_javascript: cost_of_javascript.nanos(),
+        cpp_duration: self_time.nanos(),
+        track: track_name(tracks, zone.track_id),
+        boundary: zones.name(start_of_cpp).to_string(),
+        diagnostics,
+    });
+}
+
+/// Every instance of the node that could be measured, then a summary of how many were measured
+/// and dropped, and with `emit_dropped` the rows of the dropped instances.  Fails if more than
+/// `max_drop_ratio` of the instances were dropped.
+pub(crate) fn cost_query(
+    cost: &Cost,
+    config: &QueryConfig,
+    tracks: &[Track],
+    zones: &Zones,
+    rows: &dyn RowSource,
+) -> Result<Vec<QueryResult>, TimelineError> {
+    let model = CostModel {
+        ignores: zones.symbols(&config.ignores),
+        boundaries: fallback_symbols(zones, &cost.boundaries, &DEFAULT_BOUNDARIES),
+        impl_args: fallback_symbols(zones, &cost.impl_args, &DEFAULT_IMPL_ARGS),
+    };
+
     let mut out = vec![];
-    let tmp_vec = &vec![];
+    let mut dropped = vec![];
+    let mut summary = CostSummary {
+        node: cost.node.as_str().to_string(),
+        ..Default::default()
+    };
+
+    for zone in find_node("Cost", &cost.node, zones)?.iter().filter_map(|idx| zones.get(*idx)) {
+        match cost_of(zone, &model, tracks, zones) {
+            Ok(result) => {
+                summary.matched += 1;
+                for diagnostic in &result.diagnostics {
+                    match diagnostic {
+                        Diagnostic::NoImplArg => summary.no_impl_arg += 1,
+                        Diagnostic::JavascriptUnderflow { .. } => summary.negative_javascript += 1,
+                        _ => {}
+                    }
+                }
+                out.push(QueryResult::Cost(result));
+            }
+            Err(reason) => {
+                debug!("dropping ({}): {} from cost_query, {}", zone.idx, zones.name(zone), reason);
+                summary.dropped += 1;
+                match reason {
+                    DropReason::NoBoundary => summary.no_boundary += 1,
+                }
+                if cost.emit_dropped.unwrap_or(false) {
+                    dropped.push(QueryResult::Dropped(DroppedZone {
+                        reason,
+                        row: rows.row(zone, zones.names())?,
+                    }));
+                }
+            }
+        }
+    }
+
+    let total = summary.matched + summary.dropped;
+    if let Some(max_ratio) = cost.max_drop_ratio {
+        if total > 0 && summary.dropped as f64 / total as f64 > max_ratio {
+            return Err(TimelineError::DropRatioExceeded {
+                node: summary.node,
+                dropped: summary.dropped,
+                total,
+                max_ratio,
+            });
+        }
+    }
+
+    out.push(QueryResult::CostSummary(summary));
+    out.append(&mut dropped);
+
+    return Ok(out);
+}
+
+/// Groups every measurement of `results` by name and metric, keeping the order each name was
+/// first seen in.
+pub fn group_samples(results: &[QueryResult]) -> Vec<(&str, &'static str, Vec<u64>)> {
+    let mut groups: Vec<(&str, &'static str, Vec<u64>)> = vec![];
+    let mut lookup: HashMap<(&str, &'static str), usize> = HashMap::new();
+
+    for (name, metric, value) in results.iter().flat_map(|r| r.samples()) {
+        let group = *lookup.entry((name, metric)).or_insert_with(|| {
+            groups.push((name, metric, vec![]));
+            return groups.len() - 1;
+        });
+        groups[group].2.push(value);
+    }
+
+    return groups;
+}
+
+pub fn aggregate_results(results: &[QueryResult], percentiles: &[f64]) -> Vec<QueryResult> {
+    return group_samples(results)
+        .into_iter()
+        .map(|(name, metric, samples)| {
+            return QueryResult::Aggregate(AggregateResult {
+                name: name.to_string(),
+                metric,
+                summary: Summary::new(&samples, percentiles),
+            });
+        })
+        .collect();
+}
+
+fn verdict(median: &Change, p_value: Option<f64>, interval: Option<Interval>, threshold: &DiffThreshold) -> Verdict {
+    let (p_value, interval) = match (p_value, interval) {
+        (Some(p_value), Some(interval)) if p_value < threshold.alpha => (p_value, interval),
+        _ => return Verdict::Unchanged,
+    };
+    debug!("significant change of {} (p={})", median, p_value);
+
+    // a median moving away from zero is always more than min_change percent.
+    let percent = median.percent.unwrap_or(median.delta.signum() * f64::INFINITY);
+    if interval.low > 0.0 && percent >= threshold.min_change {
+        return Verdict::Regressed;
+    }
+    if interval.high < 0.0 && percent <= -threshold.min_change {
+        return Verdict::Improved;
+    }
+
+    return Verdict::Unchanged;
+}
+
+/// Compares the results of the same query run on a baseline and a candidate capture, one diff per
+/// name and metric.  Names are kept in the order the baseline has them, followed by the ones only
+/// the candidate has.
+pub fn diff_results(baseline: &[QueryResult], candidate: &[QueryResult], threshold: &DiffThreshold) -> Vec<QueryResult> {
+    let baseline = group_samples(baseline);
+    let candidate = group_samples(candidate);
 
-    for zone_idx in filter_by_name(zones, &cost.node) {
-        let zone = zones.get(zone_idx).unwrap();
-        let self_time = calculate_self_time(zone.idx, zones, &tmp_vec, &config.ignores);
-        let parents = get_parents(zones, zone_idx);
-        let start_of_cpp = get_start_of_cpp(zones, &parents);
+    let mut keys = baseline.iter().map(|(name, metric, _)| (*name, *metric)).collect::<Vec<(&str, &'static str)>>();
+    for (name, metric, _) in &candidate {
+        if !keys.contains(&(*name, *metric)) {
+            keys.push((*name, *metric));
+        }
+    }
+
+    fn samples<'a>(groups: &'a [(&str, &'static str, Vec<u64>)], name: &str, metric: &str) -> &'a [u64] {
+        return groups
+            .iter()
+            .find(|(n, m, _)| *n == name && *m == metric)
+            .map(|(_, _, samples)| samples.as_slice())
+            .unwrap_or(&[]);
+    }
+
+    return keys
+        .into_iter()
+        .map(|(name, metric)| {
+            let (baseline, candidate) = (samples(&baseline, name, metric), samples(&candidate, name, metric));
+            let before = Summary::new(baseline, &[95.0]);
+            let after = Summary::new(candidate, &[95.0]);
+            let median = Change::new(before.median, after.median);
+
+            let p_value = mann_whitney_u(candidate, baseline).map(|test| test.p_value);
+            let median_difference =
+                bootstrap_median_difference(baseline, candidate, threshold.resamples, 1.0 - threshold.alpha);
+
+            return QueryResult::Diff(DiffResult {
+                name: name.to_string(),
+                metric,
+                count: Change::new(before.count as f64, after.count as f64),
+                mean: Change::new(before.mean, after.mean),
+                p95: Change::new(before.percentiles[0].value, after.percentiles[0].value),
+                verdict: verdict(&median, p_value, median_difference, threshold),
+                median,
+                p_value,
+                median_difference,
+            });
+        })
+        .collect();
+}
+
+pub(crate) fn aggregate_query(
+    aggregate: &Aggregate,
+    config: &QueryConfig,
+    tracks: &[Track],
+    zones: &Zones,
+    rows: &dyn RowSource,
+) -> Result<Vec<QueryResult>, TimelineError> {
+    let results = run_query(&aggregate.query, config, tracks, zones, rows)?;
+    let percentiles = aggregate.percentiles.as_deref().unwrap_or(&DEFAULT_PERCENTILES);
+
+    return Ok(aggregate_results(&results, percentiles));
+}
+
+// an io error while writing `file` says which file it was.
+fn naming_file(file: &str, error: TimelineError) -> TimelineError {
+    return match error {
+        TimelineError::Io(source) => TimelineError::WriteFile {
+            file: file.to_string(),
+            source,
+        },
+        error => error,
+    };
+}
 
-        // this isn't a proper measurement
-        if start_of_cpp.is_none() {
-            debug!("dropping ({}): {} from cost_query due to missing start of cpp", zone.idx, &zone.name);
+// zone names can hold anything, keep file names to something every filesystem accepts.
+fn file_name(name: &str) -> String {
+    return name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+}
+
+pub(crate) fn histogram_query(
+    query: &Histogram,
+    config: &QueryConfig,
+    tracks: &[Track],
+    zones: &Zones,
+    rows: &dyn RowSource,
+) -> Result<Vec<QueryResult>, TimelineError> {
+    let results = run_query(&query.query, config, tracks, zones, rows)?;
+    let scale = query.scale.unwrap_or(Scale::Linear);
+    let bins = query.bins.unwrap_or(if scale == Scale::Hdr { 8 } else { 40 });
+
+    if let Some(dir) = &query.svg_dir {
+        create_dir_all(dir).map_err(|e| naming_file(dir, e.into()))?;
+    }
+
+    let mut out = vec![];
+    for (name, metric, mut samples) in group_samples(&results) {
+        if query.metric.as_deref().is_some_and(|m| m != metric) {
             continue;
         }
-        let start_of_cpp = start_of_cpp.unwrap();
-        let start_of_cpp = zones.get(start_of_cpp).unwrap();
 
-        let to_cpp_total_time = calculate_total_time(start_of_cpp, zones, &config.ignores);
+        let trimmed = match query.reduce_by_std {
+            Some(deviations) => trim_outliers(&mut samples, deviations),
+            None => 0,
+        };
+        let histogram = histogram::Histogram::new(&samples, scale, bins, trimmed);
 
-        let impl_arg = get_impl_arg(zones, start_of_cpp.idx);
-        let impl_time = if let Some(arg) = impl_arg {
-            calculate_self_time(arg, zones, &tmp_vec, &config.ignores)
-        } else {
-            0
+        let svg = match &query.svg_dir {
+            Some(dir) => {
+                let path = Path::new(dir).join(format!("{}.{}.svg", file_name(name), metric));
+                let path = path.display().to_string();
+                info!("writing histogram of {} to {}", name, path);
+                File::create(&path)
+                    .map_err(TimelineError::from)
+                    .and_then(|file| histogram.write_svg(&format!("{} {}", name, metric), BufWriter::new(file)))
+                    .map_err(|e| naming_file(&path, e))?;
+                Some(path)
+            }
+            None => None,
         };
 
-        out.push(QueryResult::Cost(CostResult {
-            name: zone.name.clone(),
-            cost_of_args: impl_time,
-            cost_of_javascript: to_cpp_total_time - self_time,
-            cpp_duration: self_time,
+        out.push(QueryResult::Histogram(HistogramResult {
+            name: name.to_string(),
+            metric,
+            svg,
+            histogram,
         }));
     }
 
-    return out;
+    return Ok(out);
+}
+
+/// Runs `query` over the zones inside `window`, or over every zone when it has none.
+pub fn with_window<T>(
+    name: &'static str,
+    window: &Window,
+    zones: &Zones,
+    query: impl FnOnce(&Zones) -> Result<T, TimelineError>,
+) -> Result<T, TimelineError> {
+    return match window.resolve(zones)? {
+        Some((from, to)) => {
+            info!("{} limited to zones within {} - {}", name, from, to);
+            query(&zones.within(from, to))
+        }
+        None => query(zones),
+    };
 }
 
 pub fn run_query(
     query: &Query,
     config: &QueryConfig,
-    zones: &Vec<Zone>,
-) -> Result<(), TimelineError> {
-    let results = match query {
-        Query::SelfTime(s) => self_time_query(&s, config, zones),
-        Query::Reduce(r) => reduce_query(&r, zones),
-        Query::Stat(s) => stat_query(&s, zones),
-        Query::Cost(c) => cost_query(&c, config, zones),
-    };
-
-    for result in results {
-        println!("{}", result);
-    }
-
-    return Ok(());
+    tracks: &[Track],
+    zones: &Zones,
+    rows: &dyn RowSource,
+) -> Result<Vec<QueryResult>, TimelineError> {
+    return with_window(query.name(), query.window(), zones, |zones| {
+        return match query {
+            Query::SelfTime(s) => self_time_query(s, config, tracks, zones),
+            Query::Reduce(r) => reduce_query(r, zones, rows),
+            Query::Stat(s) => stat_query(s, tracks, zones),
+            Query::Cost(c) => cost_query(c, config, tracks, zones, rows),
+            Query::Export(e) => export_query(e, tracks, zones),
+            Query::Aggregate(a) => aggregate_query(a, config, tracks, zones, rows),
+            Query::Histogram(h) => histogram_query(h, config, tracks, zones, rows),
+        };
+    });
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{zone_search::set_zone_idx, tests::TestZone};
+    use crate::tests::{from_csv_strings, TestZones};
 
-    #[test]
-    fn test_self_time_query() {
-        let mut zones = vec![
-            Zone::new("foo".to_string(), 8, 20, 0),
-            Zone::new("foo2".to_string(), 10, 50, 0),
-            Zone::new("foo".to_string(), 30, 40, 0),
-            Zone::new("foo".to_string(), 48, 55, 0),
-        ];
+    fn tracks() -> Vec<Track> {
+        return vec![Track { name: "Main Thread".to_string(), id: 0 }];
+    }
 
-        set_zone_idx(&mut zones);
+    fn config(ignores: &[&str]) -> QueryConfig {
+        return QueryConfig {
+            ignores: ignores.iter().map(|name| (*name).into()).collect(),
+            ..QueryConfig::default()
+        };
+    }
+
+    #[test]
+    fn test_self_time_query() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo2", 10, 50, 0),
+            ("foo", 30, 40, 0),
+            ("foo", 48, 55, 0),
+        ]);
 
         let self_time = SelfTime {
-            partial_ignore: vec!["foo".to_string()],
-            node: "foo2".to_string(),
+            partial_ignore: vec!["foo".into()],
+            node: "foo2".into(),
+            window: Window::default(),
         };
 
-        let config = QueryConfig {
-            ignores: vec![],
-            queries: vec![],
-        };
+        let config = QueryConfig::default();
 
-        let res = self_time_query(&self_time, &config, &zones);
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
 
         assert_eq!(res.len(), 1);
         assert_eq!(
-            *res.get(0).unwrap(),
+            *res.first().unwrap(),
             QueryResult::DataPoint(DataPoint {
                 query: "SelfTime".to_string(),
                 name: "foo2".to_string(),
                 count: 28,
                 additional_data: None,
+                track: "Main Thread".to_string(),
+                diagnostics: vec![],
             })
-        )
+        );
+
+        return Ok(());
     }
 
     #[test]
-    fn test_self_time_query_with_ignores() {
-        let mut zones = vec![
-            Zone::new("foo".to_string(), 8, 20, 0),
-            Zone::new("foo2".to_string(), 10, 50, 0),
-            Zone::new("ignore-me".to_string(), 25, 30, 0),
-            Zone::new("foo".to_string(), 30, 40, 0),
-            Zone::new("foo".to_string(), 48, 55, 0),
-        ];
-
-        set_zone_idx(&mut zones);
+    fn test_self_time_query_with_ignores() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo2", 10, 50, 0),
+            ("ignore-me", 25, 30, 0),
+            ("foo", 30, 40, 0),
+            ("foo", 48, 55, 0),
+        ]);
 
         let self_time = SelfTime {
-            partial_ignore: vec!["foo".to_string()],
-            node: "foo2".to_string(),
+            partial_ignore: vec!["foo".into()],
+            node: "foo2".into(),
+            window: Window::default(),
         };
 
-        let config = QueryConfig {
-            ignores: vec!["ignore-me".to_string()],
-            queries: vec![],
-        };
+        let config = config(&["ignore-me"]);
 
-        let res = self_time_query(&self_time, &config, &zones);
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
 
         assert_eq!(res.len(), 1);
         assert_eq!(
-            *res.get(0).unwrap(),
+            *res.first().unwrap(),
             QueryResult::DataPoint(DataPoint {
                 query: "SelfTime".to_string(),
                 name: "foo2".to_string(),
                 count: 23,
                 additional_data: None,
+                track: "Main Thread".to_string(),
+                diagnostics: vec![],
             })
-        )
+        );
+
+        return Ok(());
     }
 
     #[test]
-    fn test_reduce_query() {
-        let mut zones = vec![
-            Zone::new("excluded_outside_left".to_string(), 6, 20, 0),
-            Zone::new("included_partial_left".to_string(), 8, 26, 0),
-            Zone::new("excluded_container".to_string(), 10, 51, 0),
-            Zone::new("root".to_string(), 25, 50, 0),
-            Zone::new("contained".to_string(), 30, 40, 0),
-            Zone::new("included_partial_right".to_string(), 48, 55, 0),
-            Zone::new("excluded_outside_right".to_string(), 51, 60, 0),
-        ]
-        .into_iter()
-        .map(|mut zone| {
-            zone.original_csv = format!("{}", zone.name);
-            return zone;
-        })
-        .collect::<Vec<Zone>>();
+    fn test_self_time_query_with_nested_ignores() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 0, 100, 0),
+            ("ignore-me", 10, 50, 0),
+            ("ignore-me", 20, 30, 0),
+            ("bar", 60, 90, 0),
+            ("ignore-me", 70, 75, 0),
+        ]);
+
+        let self_time = SelfTime {
+            partial_ignore: vec![],
+            node: "foo".into(),
+            window: Window::default(),
+        };
+
+        let config = config(&["ignore-me"]);
+
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
+
+        assert_eq!(
+            *res.first().unwrap(),
+            QueryResult::DataPoint(DataPoint {
+                query: "SelfTime".to_string(),
+                name: "foo".to_string(),
+                count: 55,
+                additional_data: None,
+                track: "Main Thread".to_string(),
+                diagnostics: vec![],
+            })
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_self_time_query_with_ignores_on_other_tracks() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 0, 100, 0),
+            ("ignore-me", 10, 50, 1),
+            ("ignore-me", 20, 30, 0),
+            ("partial", 90, 120, 1),
+        ]);
+
+        let self_time = SelfTime {
+            partial_ignore: vec!["partial".into()],
+            node: "foo".into(),
+            window: Window::default(),
+        };
+
+        let config = config(&["ignore-me"]);
+
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].to_string(), "SelfTime,foo,50,,Main Thread,");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_self_time_query_with_overlapping_ignores() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 0, 100, 0),
+            ("ignore-me", 10, 50, 1),
+            ("ignore-me", 40, 60, 0),
+            ("ignore-me", 45, 48, 1),
+        ]);
+
+        let self_time = SelfTime {
+            partial_ignore: vec![],
+            node: "foo".into(),
+            window: Window::default(),
+        };
+
+        let config = config(&["ignore-me"]);
 
-        set_zone_idx(&mut zones);
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
+
+        assert_eq!(res[0].to_string(), "SelfTime,foo,50,,Main Thread,");
+        assert_eq!(calculations::get_ignored(&zones, 0, &zones.symbols(&["ignore-me".into()])), vec![1, 2]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_self_time_query_with_ignore_inside_partial() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 0, 100, 0),
+            ("ignore-me", 40, 100, 0),
+            ("partial", 50, 150, 0),
+        ]);
+
+        let self_time = SelfTime {
+            partial_ignore: vec!["partial".into()],
+            node: "foo".into(),
+            window: Window::default(),
+        };
+
+        let config = config(&["ignore-me"]);
+
+        let res = self_time_query(&self_time, &config, &tracks(), &zones)?;
+
+        assert_eq!(format!("{}", res[0]), "SelfTime,foo,40,,Main Thread,");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_reduce_query() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("excluded_outside_left", 6, 20, 0),
+            ("included_partial_left", 8, 26, 0),
+            ("excluded_container", 10, 51, 0),
+            ("root", 25, 50, 0),
+            ("contained", 30, 40, 0),
+            ("included_partial_right", 48, 55, 0),
+            ("excluded_outside_right", 51, 60, 0),
+        ]);
 
         let reduce = Reduce {
-            node: "root".to_string(),
+            node: "root".into(),
             ignore_count: Some(0),
+            window: Window::default(),
         };
 
-        let res = reduce_query(&reduce, &zones)
+        let res = reduce_query(&reduce, &zones, &rows)?
             .into_iter()
             .map(|qr| {
                 return match qr {
@@ -263,34 +725,47 @@ mod test {
             .collect::<Vec<String>>();
 
         assert_eq!(res.len(), 4);
-        assert_eq!(res.get(0).unwrap(), "included_partial_left"); // left
-        assert_eq!(res.get(1).unwrap(), "included_partial_right"); // right
-        assert_eq!(res.get(2).unwrap(), "contained"); // right
-        assert_eq!(res.get(3).unwrap(), "root"); // right
+        assert_eq!(res.first().unwrap(), "TM_ZONE,0,included_partial_left,8,26"); // left
+        assert_eq!(res.get(1).unwrap(), "TM_ZONE,0,included_partial_right,48,55"); // right
+        assert_eq!(res.get(2).unwrap(), "TM_ZONE,0,contained,30,40"); // right
+        assert_eq!(res.get(3).unwrap(), "TM_ZONE,0,root,25,50"); // right
+
+        return Ok(());
     }
 
     #[test]
-    fn test_stat_query() {
-        let mut zones = vec![
-            Zone::new("foo".to_string(), 6, 20, 0),
-            Zone::new("foo".to_string(), 8, 26, 0),
-            Zone::new("foo2".to_string(), 10, 51, 0),
-            Zone::new("foo3".to_string(), 11, 51, 0),
-        ]
-        .into_iter()
-        .map(|mut zone| {
-            zone.original_csv = format!("{}", zone.name);
-            return zone;
-        })
-        .collect::<Vec<Zone>>();
+    fn test_missing_query_node() {
+        let (zones, rows) = Zones::with_csv_rows(&[("foo", 0, 10, 0), ("foo", 20, 30, 0)]);
+        let config = QueryConfig::default();
 
-        set_zone_idx(&mut zones);
+        let typo: Query = serde_json::from_str(r#"{"type": "Stat", "node": "fooo"}"#).unwrap();
+        assert!(matches!(
+            run_query(&typo, &config, &tracks(), &zones, &rows),
+            Err(TimelineError::MissingQueryNode { query: "Stat", .. })
+        ));
+
+        let past_the_end: Query = serde_json::from_str(r#"{"type": "Reduce", "node": "foo", "ignore_count": 2}"#).unwrap();
+        assert!(matches!(
+            run_query(&past_the_end, &config, &tracks(), &zones, &rows),
+            Err(TimelineError::MissingQueryInstance { ignore_count: 2, found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_stat_query() -> Result<(), TimelineError> {
+        let zones = Zones::named(&[
+            ("foo", 6, 20, 0),
+            ("foo", 8, 26, 0),
+            ("foo2", 10, 51, 0),
+            ("foo3", 11, 51, 0),
+        ]);
 
         let stat = Stat {
-            node: "foo".to_string(),
+            node: "foo".into(),
+            window: Window::default(),
         };
 
-        let res = stat_query(&stat, &zones)
+        let res = stat_query(&stat, &tracks(), &zones)?
             .into_iter()
             .map(|qr| {
                 return format!("{}", qr);
@@ -298,14 +773,287 @@ mod test {
             .collect::<Vec<String>>();
 
         assert_eq!(res.len(), 2);
-        assert_eq!(res.get(0).unwrap(), "foo,14,6,20"); // left
-        assert_eq!(res.get(1).unwrap(), "foo,18,8,26"); // left
+        assert_eq!(res.first().unwrap(), "foo,14,6,20,Main Thread"); // left
+        assert_eq!(res.get(1).unwrap(), "foo,18,8,26,Main Thread"); // left
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_aggregate_query() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("foo", 0, 10, 0),
+            ("bar", 5, 8, 0),
+            ("foo", 20, 40, 0),
+            ("foo", 50, 80, 0),
+        ]);
+
+        let aggregate: Query = serde_json::from_str(
+            r#"{"type": "Aggregate", "percentiles": [50], "query": {"type": "Stat", "node": "foo"}}"#,
+        )?;
+        let config = QueryConfig::default();
+
+        let res = run_query(&aggregate, &config, &tracks(), &zones, &rows)?;
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(format!("{}", res[0]), "foo,duration,3,10,30,20.00,10.00,20.00,p50=20.00");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_diff_results() {
+        let stat = |name: &str, duration: u64| {
+            return QueryResult::Stat(StatResult {
+                name: name.to_string(),
+                start_time: 0,
+                end_time: duration,
+                duration,
+                track: "Main Thread".to_string(),
+            });
+        };
+        let baseline = vec![stat("foo", 10), stat("foo", 20), stat("foo", 30), stat("gone", 5)];
+        let candidate = vec![stat("new", 1), stat("foo", 10), stat("foo", 14), stat("foo", 18), stat("foo", 22)];
+
+        let res = diff_results(&baseline, &candidate, &DiffThreshold::default())
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            res,
+            vec![
+                "foo,duration,count=3->4 (+1, +33.33%),mean=20.00->16.00 (-4.00, -20.00%),median=20.00->16.00 (-4.00, -20.00%),p95=29.00->21.40 (-7.60, -26.21%),p=0.7213,median_ci=[-18.00 10.00],unchanged",
+                "gone,duration,count=1->0 (-1, -100.00%),mean=5.00->0.00 (-5.00, -100.00%),median=5.00->0.00 (-5.00, -100.00%),p95=5.00->0.00 (-5.00, -100.00%),p=n/a,median_ci=n/a,unchanged",
+                "new,duration,count=0->1 (+1, n/a),mean=0.00->1.00 (+1.00, n/a),median=0.00->1.00 (+1.00, n/a),p95=0.00->1.00 (+1.00, n/a),p=n/a,median_ci=n/a,unchanged",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_verdicts() {
+        let stats = |name: &str, durations: Vec<u64>| {
+            return durations
+                .into_iter()
+                .map(|duration| {
+                    return QueryResult::Stat(StatResult {
+                        name: name.to_string(),
+                        start_time: 0,
+                        end_time: duration,
+                        duration,
+                        track: "Main Thread".to_string(),
+                    });
+                })
+                .collect::<Vec<QueryResult>>();
+        };
+        let noise = (0..100).map(|i| i % 7).collect::<Vec<u64>>();
+
+        let mut baseline = stats("slower", noise.iter().map(|n| 100 + n).collect());
+        baseline.append(&mut stats("faster", noise.iter().map(|n| 100 + n).collect()));
+        baseline.append(&mut stats("same", noise.iter().map(|n| 100 + n).collect()));
+        baseline.append(&mut stats("barely", noise.iter().map(|n| 100 + n).collect()));
+        let mut candidate = stats("slower", noise.iter().map(|n| 120 + n).collect());
+        candidate.append(&mut stats("faster", noise.iter().map(|n| 80 + n).collect()));
+        candidate.append(&mut stats("same", noise.iter().rev().map(|n| 100 + n).collect()));
+        candidate.append(&mut stats("barely", noise.iter().map(|n| 102 + n).collect()));
+
+        let verdicts = diff_results(&baseline, &candidate, &DiffThreshold::default())
+            .into_iter()
+            .map(|r| {
+                return match r {
+                    QueryResult::Diff(d) => (d.name, d.verdict),
+                    _ => panic!("expected a diff"),
+                };
+            })
+            .collect::<Vec<(String, Verdict)>>();
+
+        // barely is significant, but under min_change.
+        assert_eq!(
+            verdicts,
+            vec![
+                ("slower".to_string(), Verdict::Regressed),
+                ("faster".to_string(), Verdict::Improved),
+                ("same".to_string(), Verdict::Unchanged),
+                ("barely".to_string(), Verdict::Unchanged),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_query() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("foo", 0, 10, 0),
+            ("bar", 2, 4, 0),
+            ("foo", 20, 40, 0),
+        ]);
+        let output = std::env::temp_dir().join("timeline_test_export_query.json").display().to_string();
+        let config = QueryConfig::default();
+
+        let export: Query = serde_json::from_str(&format!(r#"{{"type": "Export", "output": {:?}, "node": "foo"}}"#, output))?;
+        let res = run_query(&export, &config, &tracks(), &zones, &rows)?;
+        assert_eq!(res, vec![QueryResult::Exported(ExportResult { file: output.clone(), zones: 2 })]);
+        std::fs::remove_file(&output)?;
+
+        let missing = std::env::temp_dir().join("timeline_missing_dir").join("out.json").display().to_string();
+        let export: Query = serde_json::from_str(&format!(r#"{{"type": "Export", "output": {:?}}}"#, missing))?;
+        match run_query(&export, &config, &tracks(), &zones, &rows) {
+            Err(TimelineError::WriteFile { file, .. }) => assert_eq!(file, missing),
+            _ => unreachable!(),
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_histogram_query() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("foo", 0, 10, 0),
+            ("foo", 20, 40, 0),
+            ("foo", 50, 60, 0),
+            ("foo", 70, 1070, 0),
+        ]);
+
+        let histogram: Query = serde_json::from_str(
+            r#"{"type": "Histogram", "bins": 2, "reduce_by_std": 1, "query": {"type": "Stat", "node": "foo"}}"#,
+        )?;
+        let config = QueryConfig::default();
+
+        let res = run_query(&histogram, &config, &tracks(), &zones, &rows)?;
+
+        assert_eq!(res.len(), 1);
+        match &res[0] {
+            QueryResult::Histogram(h) => {
+                assert_eq!(h.histogram.trimmed, 1);
+                assert_eq!(
+                    h.histogram.buckets.iter().map(|b| (b.low, b.high, b.count)).collect::<Vec<(f64, f64, usize)>>(),
+                    vec![(10.0, 15.0, 2), (15.0, 20.0, 1)]
+                );
+            }
+            _ => unreachable!(),
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_cost_query_fallbacks() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("Bindings.Call", 0, 100, 0),
+            ("convertArgs", 10, 20, 0),
+            ("Bridge.get", 30, 60, 0),
+            ("Other.Call", 200, 300, 0),
+            ("Bridge.get", 210, 220, 0),
+            ("Bridge.get", 400, 410, 0),
+        ]);
+
+        let cost: Query = serde_json::from_str(
+            r#"{
+                "type": "Cost",
+                "node": "Bridge.get",
+                "boundaries": ["Missing.Call", {"glob": "*.Call"}],
+                "impl_args": ["toImplArgs2", "convertArgs"],
+                "emit_dropped": true
+            }"#,
+        )?;
+        let config = QueryConfig::default();
+
+        let res = run_query(&cost, &config, &tracks(), &zones, &rows)?
+            .iter()
+            .map(|qr| format!("{}", qr))
+            .collect::<Vec<String>>();
+
+        assert_eq!(res, vec![
+            "Bridge.get,70,10,30,Main Thread,Bindings.Call,",
+            "Bridge.get,90,0,10,Main Thread,Other.Call,no_impl_arg",
+            "Bridge.get,matched=2,dropped=1,no_boundary=1,no_impl_arg=1,negative_javascript=0",
+            "dropped,no_boundary,TM_ZONE,0,Bridge.get,400,410",
+        ]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_cost_query_drop_ratio() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("V8.Builtin_HandleApiCall", 0, 100, 0),
+            ("toImplArgs2", 10, 20, 0),
+            ("Bridge.get", 30, 60, 0),
+            ("Bridge.get", 400, 410, 0),
+        ]);
+        let config = QueryConfig::default();
+
+        let half: Query = serde_json::from_str(r#"{"type": "Cost", "node": "Bridge.get", "max_drop_ratio": 0.5}"#)?;
+        let res = run_query(&half, &config, &tracks(), &zones, &rows)?
+            .iter()
+            .map(|qr| format!("{}", qr))
+            .collect::<Vec<String>>();
+        // the summary is there without emit_dropped, only the dropped rows are left out.
+        assert_eq!(res[1..], ["Bridge.get,matched=1,dropped=1,no_boundary=1,no_impl_arg=0,negative_javascript=0"]);
+
+        let strict: Query = serde_json::from_str(r#"{"type": "Cost", "node": "Bridge.get", "max_drop_ratio": 0.1}"#)?;
+        assert!(matches!(
+            run_query(&strict, &config, &tracks(), &zones, &rows),
+            Err(TimelineError::DropRatioExceeded { dropped: 1, total: 2, .. })
+        ));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_cost_query_negative_javascript() -> Result<(), TimelineError> {
+        // the ignored zone takes most of the boundary but only overlaps the node, so the node's
+        // self time is longer than what is left of the boundary.
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("V8.Builtin_HandleApiCall", 0, 100, 0),
+            ("ignore-me", 0, 90, 1),
+            ("toImplArgs2", 10, 20, 0),
+            ("Bridge.get", 80, 100, 0),
+        ]);
+        let config = config(&["ignore-me"]);
+
+        let cost: Query = serde_json::from_str(r#"{"type": "Cost", "node": "Bridge.get", "emit_dropped": true}"#)?;
+        let res = run_query(&cost, &config, &tracks(), &zones, &rows)?
+            .iter()
+            .map(|qr| format!("{}", qr))
+            .collect::<Vec<String>>();
+
+        assert_eq!(res, vec![
+            "Bridge.get,0,10,20,Main Thread,V8.Builtin_HandleApiCall,javascript_underflow(20 from 10)",
+            "Bridge.get,matched=1,dropped=0,no_boundary=0,no_impl_arg=0,negative_javascript=1",
+        ]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_query_window() -> Result<(), TimelineError> {
+        let (zones, rows) = Zones::with_csv_rows(&[
+            ("startup", 0, 100, 0),
+            ("foo", 10, 20, 0),
+            ("playback", 200, 400, 0),
+            ("foo", 250, 260, 0),
+            ("foo", 390, 410, 0),
+        ]);
+
+        let stat: Query = serde_json::from_str(
+            r#"{"type": "Stat", "node": "foo", "window": {"from": {"marker": "playback"}, "to": {"marker": "playback"}}}"#,
+        )?;
+        let config = QueryConfig::default();
+
+        let res = run_query(&stat, &config, &tracks(), &zones, &rows)?
+            .iter()
+            .map(|qr| format!("{}", qr))
+            .collect::<Vec<String>>();
+
+        assert_eq!(res, vec!["foo,10,250,260,Main Thread"]);
+
+        return Ok(());
     }
 
     #[test]
-    fn test_cost_query() -> Result<(), std::num::ParseIntError> {
+    fn test_cost_query() -> Result<(), TimelineError> {
         // taken from actual data from the odroid. for a single V8.Builtin_HandleApiCall
-        let mut zones = Zone::from_csv_strings(vec![
+        let (zones, rows) = Zones::with_csv_rows(&from_csv_strings(&[
             "TM_ZONE,65536,V8TracingController.AddTraceEvent,1642630333023844044,1642630333023848502,10,0x0,0xff0000ff,include/nrdbase/Telemetry.h,86,0,0",
             "TM_ZONE,65536,V8.ExternalCallback,1642630333023852127,1642630333023877210,7,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0",
             "TM_ZONE,65536,INST_DataBufferBridge_CLASSgetUint8,1642630333023854211,1642630333023867793,8,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0",
@@ -313,19 +1061,21 @@ mod test {
             "TM_ZONE,65536,DataBufferBridge.getUint8,1642630333023863169,1642630333023865376,9,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0",
             "TM_ZONE,65536,V8TracingController.AddTraceEvent,1642630333023869126,1642630333023873710,10,0x0,0xff0000ff,include/nrdbase/Telemetry.h,86,0,0",
             "TM_ZONE,65536,V8.Builtin_HandleApiCall,1642630333023839586,1642630333023880460,6,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0",
-        ])?;
-        set_zone_idx(&mut zones);
+        ])?);
 
         let result = cost_query(&Cost {
-            node: "DataBufferBridge.getUint8".to_string(),
-        }, &QueryConfig {
-            ignores: vec!["V8TracingController.AddTraceEvent".to_string()],
-            queries: vec![],
-        }, &zones);
+            node: "DataBufferBridge.getUint8".into(),
+            boundaries: None,
+            impl_args: None,
+            emit_dropped: None,
+            max_drop_ratio: None,
+            window: Window::default(),
+        }, &config(&["V8TracingController.AddTraceEvent"]), &[Track { name: "Main Thread".to_string(), id: 65536 }], &zones, &rows)?;
 
-        assert_eq!(result.len(), 1);
+        assert_eq!(result.len(), 2);
+        assert_eq!(format!("{}", result[1]), "DataBufferBridge.getUint8,matched=1,dropped=0,no_boundary=0,no_impl_arg=0,negative_javascript=0");
 
-        match result.get(0).unwrap() {
+        match result.first().unwrap() {
             QueryResult::Cost(c) => {
                 let cpp_duration = 1642630333023865376u64 - 1642630333023863169u64;
                 let args = 1642630333023858544u64 - 1642630333023856211u64;
@@ -337,6 +1087,8 @@ mod test {
                 assert_eq!(c.cpp_duration, cpp_duration);
                 assert_eq!(c.cost_of_args, args);
                 assert_eq!(c.cost_of_javascript, to_cpp_duration - cpp_duration - tracing);
+                assert_eq!(c.track, "Main Thread");
+                assert_eq!(c.boundary, "V8.Builtin_HandleApiCall");
             }
             _ => unreachable!(),
         }
@@ -1,23 +1,30 @@
-use std::{str::FromStr, fs::File, io::BufReader};
+use std::{str::FromStr, io::BufReader};
 
 use serde::Deserialize;
 
-use super::query::Query;
+use crate::{error::TimelineError, parse::open_file, pattern::NamePattern};
 
-#[derive(Debug, Deserialize)]
+use super::{budget::Budget, types::Query};
+
+#[derive(Debug, Default, Deserialize)]
 pub struct QueryConfig {
-    pub ignores: Vec<String>,
+    pub ignores: Vec<NamePattern>,
     pub queries: Vec<Query>,
+
+    /// Limits on the results, checked after every query ran, see `Budget`.
+    #[serde(default)]
+    pub budgets: Vec<Budget>,
 }
 
+/// Reads the query file at the given path.
 impl FromStr for QueryConfig {
-    type Err = std::io::Error;
+    type Err = TimelineError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let file = File::open(s)?;
-        let reader = BufReader::new(file);
-        return Ok(serde_json::from_reader(reader)?);
+        let reader = BufReader::new(open_file(s)?);
+        return serde_json::from_reader(reader).map_err(|source| TimelineError::InvalidQueryFile {
+            file: s.to_string(),
+            source,
+        });
     }
 }
-
-
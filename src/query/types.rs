@@ -0,0 +1,499 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{histogram::{self, Scale}, pattern::NamePattern, stats::{Interval, Summary}};
+
+use super::{duration::Diagnostic, window::Window};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stat {
+    pub node: NamePattern,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reduce {
+    pub node: NamePattern,
+    pub ignore_count: Option<usize>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+/// Splits the time of every `node` into the c++ it runs, the javascript between it and the
+/// `boundaries` zone where javascript called into c++, and the `impl_args` zone that converted
+/// the arguments.  Both lists are tried in order until one matches and default to the V8 names in
+/// `calculations`.  The results are followed by a summary of how many instances were measured
+/// and dropped, and with `emit_dropped` by the rows of the dropped ones.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cost {
+    pub node: NamePattern,
+    pub boundaries: Option<Vec<NamePattern>>,
+    pub impl_args: Option<Vec<NamePattern>>,
+    pub emit_dropped: Option<bool>,
+    pub max_drop_ratio: Option<f64>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfTime {
+    pub node: NamePattern,
+    pub partial_ignore: Vec<NamePattern>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+/// Writes zones out as Chrome Trace Event Format json, which chrome://tracing and the Perfetto UI
+/// can open.  With a `node` only the zones a Reduce of that node would print are written.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    pub output: String,
+    pub node: Option<NamePattern>,
+    pub ignore_count: Option<usize>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+/// Runs `query` and summarises its results per zone name instead of printing every instance.
+/// `percentiles` defaults to p90, p95, p99 and p99.9.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aggregate {
+    pub query: Box<Query>,
+    pub percentiles: Option<Vec<f64>>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+/// Runs `query` and buckets every measurement of its results per zone name.  `bins` is the number
+/// of buckets, 40 by default, except for the hdr scale where it is the number of buckets every
+/// power of two is split in, 8 by default.  `reduce_by_std` first drops the samples more than that
+/// many standard deviations above the mean.  With `svg_dir` every histogram is also drawn to
+/// `{svg_dir}/{name}.{metric}.svg`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Histogram {
+    pub query: Box<Query>,
+    pub metric: Option<String>,
+    pub scale: Option<Scale>,
+    pub bins: Option<usize>,
+    pub reduce_by_std: Option<f64>,
+    pub svg_dir: Option<String>,
+
+    #[serde(default)]
+    pub window: Window,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Query {
+    SelfTime(SelfTime),
+    Reduce(Reduce),
+    Stat(Stat),
+    Cost(Cost),
+    Export(Export),
+    Aggregate(Aggregate),
+    Histogram(Histogram),
+}
+
+impl Query {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Query::SelfTime(_) => "SelfTime",
+            Query::Reduce(_) => "Reduce",
+            Query::Stat(_) => "Stat",
+            Query::Cost(_) => "Cost",
+            Query::Export(_) => "Export",
+            Query::Aggregate(_) => "Aggregate",
+            Query::Histogram(_) => "Histogram",
+        };
+    }
+
+    /// The `window` of the query, see `Window`.
+    pub fn window(&self) -> &Window {
+        return match self {
+            Query::SelfTime(s) => &s.window,
+            Query::Reduce(r) => &r.window,
+            Query::Stat(s) => &s.window,
+            Query::Cost(c) => &c.window,
+            Query::Export(e) => &e.window,
+            Query::Aggregate(a) => &a.window,
+            Query::Histogram(h) => &h.window,
+        };
+    }
+
+    /// Whether the results have samples a diff can compare, see `QueryResult::samples`.  The
+    /// others are summaries, rows or files, Export and Histogram would also write their files
+    /// once for each capture.
+    pub fn comparable(&self) -> bool {
+        return matches!(self, Query::SelfTime(_) | Query::Stat(_) | Query::Cost(_));
+    }
+
+    pub fn node(&self) -> Option<&str> {
+        return match self {
+            Query::SelfTime(s) => Some(s.node.as_str()),
+            Query::Reduce(r) => Some(r.node.as_str()),
+            Query::Stat(s) => Some(s.node.as_str()),
+            Query::Cost(c) => Some(c.node.as_str()),
+            Query::Export(e) => e.node.as_ref().map(|node| node.as_str()),
+            Query::Aggregate(a) => a.query.node(),
+            Query::Histogram(h) => h.query.node(),
+        };
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct DataPoint {
+    pub query: String,
+    pub name: String,
+    pub count: u64,
+    pub additional_data: Option<String>,
+    pub track: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct StatResult {
+    pub name: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub duration: u64,
+    pub track: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct CostResult {
+    pub name: String,
+    pub cpp_duration: u64,
+    pub cost_of_javascript: u64,
+    pub cost_of_args: u64,
+    pub track: String,
+    pub boundary: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Why an instance of a Cost node could not be measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropReason {
+    /// None of its parents matched a boundary.
+    NoBoundary,
+}
+
+impl Display for DropReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", match self {
+            DropReason::NoBoundary => "no_boundary",
+        });
+    }
+}
+
+/// How many instances of a Cost node were measured and how many were dropped, by reason.
+/// `no_impl_arg` and `negative_javascript` count measured instances, whose arguments or
+/// javascript were taken as zero and carry the diagnostic of the same name.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CostSummary {
+    pub node: String,
+    pub matched: usize,
+    pub dropped: usize,
+    pub no_boundary: usize,
+    pub no_impl_arg: usize,
+    pub negative_javascript: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DroppedZone {
+    pub reason: DropReason,
+    pub row: String,
+}
+
+/// The summary of one measurement of every result with the same name.  `metric` is the result
+/// field that was summarised, `count` for SelfTime, `duration` for Stat, and `cpp_duration`,
+/// `cost_of_javascript` and `cost_of_args` for Cost.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AggregateResult {
+    pub name: String,
+    pub metric: &'static str,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+/// `svg` is the file the histogram was drawn to, when the query has an `svg_dir`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HistogramResult {
+    pub name: String,
+    pub metric: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub svg: Option<String>,
+    #[serde(flatten)]
+    pub histogram: histogram::Histogram,
+}
+
+/// Where an Export wrote its trace, and how many zones it holds.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ExportResult {
+    pub file: String,
+    pub zones: usize,
+}
+
+/// How one statistic moved from the baseline capture to the candidate.  `percent` is relative to
+/// the baseline and is left out when the baseline is zero.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub baseline: f64,
+    pub candidate: f64,
+    pub delta: f64,
+    pub percent: Option<f64>,
+}
+
+impl Change {
+    pub fn new(baseline: f64, candidate: f64) -> Change {
+        let delta = candidate - baseline;
+        return Change {
+            baseline,
+            candidate,
+            delta,
+            percent: if baseline == 0.0 { None } else { Some(delta / baseline * 100.0) },
+        };
+    }
+}
+
+/// `baseline->candidate (delta, percent)`, values are printed with the formatter's precision,
+/// 2 decimals by default.
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let precision = f.precision().unwrap_or(2);
+        write!(
+            f,
+            "{:.p$}->{:.p$} ({:+.p$}, ",
+            self.baseline,
+            self.candidate,
+            self.delta,
+            p = precision
+        )?;
+        return match self.percent {
+            Some(percent) => write!(f, "{:+.2}%)", percent),
+            None => write!(f, "n/a)"),
+        };
+    }
+}
+
+/// When a diff calls a change significant.  The Mann-Whitney p value has to be below `alpha`, the
+/// `1 - alpha` bootstrap interval of the median difference must not include zero, and the median
+/// has to move by at least `min_change` percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffThreshold {
+    pub alpha: f64,
+    pub min_change: f64,
+    pub resamples: usize,
+}
+
+impl Default for DiffThreshold {
+    fn default() -> Self {
+        return DiffThreshold {
+            alpha: 0.05,
+            min_change: 5.0,
+            resamples: 1000,
+        };
+    }
+}
+
+/// Every metric is a duration, so a significantly larger median is a regression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Unchanged,
+    Improved,
+    Regressed,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", match self {
+            Verdict::Unchanged => "unchanged",
+            Verdict::Improved => "improved",
+            Verdict::Regressed => "regressed",
+        });
+    }
+}
+
+/// One measurement of every result with the same name, compared between two captures.  A name
+/// only found in one of them compares against zero, has no p value or interval and is never
+/// flagged.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DiffResult {
+    pub name: String,
+    pub metric: &'static str,
+    pub count: Change,
+    pub mean: Change,
+    pub median: Change,
+    pub p95: Change,
+    /// Mann-Whitney U p value of the candidate samples against the baseline samples.
+    pub p_value: Option<f64>,
+    /// Bootstrap confidence interval of the candidate median minus the baseline median.
+    pub median_difference: Option<Interval>,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "result")]
+pub enum QueryResult {
+    DataPoint(DataPoint),
+    OriginalCsvRow(String),
+    Stat(StatResult),
+    Cost(CostResult),
+    CostSummary(CostSummary),
+    Dropped(DroppedZone),
+    Aggregate(AggregateResult),
+    Histogram(HistogramResult),
+    Exported(ExportResult),
+    Diff(DiffResult),
+}
+
+impl QueryResult {
+    /// The name and every measurement of a result, as `(name, metric, value)`.  Original csv rows,
+    /// summaries, dropped zones, aggregates, histograms, exports and diffs have none.
+    pub fn samples(&self) -> Vec<(&str, &'static str, u64)> {
+        return match self {
+            QueryResult::DataPoint(p) => vec![(p.name.as_str(), "count", p.count)],
+            QueryResult::Stat(s) => vec![(s.name.as_str(), "duration", s.duration)],
+            QueryResult::Cost(c) => vec![
+                (c.name.as_str(), "cpp_duration", c.cpp_duration),
+                (c.name.as_str(), "cost_of_javascript", c.cost_of_javascript),
+                (c.name.as_str(), "cost_of_args", c.cost_of_args),
+            ],
+            QueryResult::OriginalCsvRow(_)
+            | QueryResult::CostSummary(_)
+            | QueryResult::Dropped(_)
+            | QueryResult::Aggregate(_)
+            | QueryResult::Histogram(_)
+            | QueryResult::Exported(_)
+            | QueryResult::Diff(_) => vec![],
+        };
+    }
+}
+
+/// Diagnostics as a single column, separated by `;`.
+pub fn join_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    return diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join(";");
+}
+
+impl Display for QueryResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryResult::DataPoint(p) => {
+                return write!(
+                    f,
+                    "{},{},{},{},{},{}",
+                    p.query,
+                    p.name,
+                    p.count,
+                    p.additional_data.as_ref().unwrap_or(&"".to_string()),
+                    p.track,
+                    join_diagnostics(&p.diagnostics),
+                );
+            }
+            QueryResult::OriginalCsvRow(s) => {
+                return write!(f, "{}", s);
+            }
+
+            QueryResult::Stat(s) => {
+                return write!(
+                    f,
+                    "{},{},{},{},{}",
+                    s.name, s.duration, s.start_time, s.end_time, s.track
+                );
+            }
+
+            QueryResult::Cost(c) => {
+                return write!(
+                    f,
+                    "{},{},{},{},{},{},{}",
+                    c.name,
+                    c.cost_of_javascript,
+                    c.cost_of_args,
+                    c.cpp_duration,
+                    c.track,
+                    c.boundary,
+                    join_diagnostics(&c.diagnostics),
+                );
+            }
+
+            QueryResult::CostSummary(s) => {
+                return write!(
+                    f,
+                    "{},matched={},dropped={},no_boundary={},no_impl_arg={},negative_javascript={}",
+                    s.node, s.matched, s.dropped, s.no_boundary, s.no_impl_arg, s.negative_javascript,
+                );
+            }
+
+            QueryResult::Dropped(d) => {
+                return write!(f, "dropped,{},{}", d.reason, d.row);
+            }
+
+            QueryResult::Aggregate(a) => {
+                let s = &a.summary;
+                write!(
+                    f,
+                    "{},{},{},{},{},{:.2},{:.2},{:.2}",
+                    a.name, a.metric, s.count, s.min, s.max, s.mean, s.stddev, s.median
+                )?;
+                for p in &s.percentiles {
+                    write!(f, ",p{}={:.2}", p.percentile, p.value)?;
+                }
+                return Ok(());
+            }
+
+            QueryResult::Histogram(h) => {
+                let histogram = &h.histogram;
+                write!(
+                    f,
+                    "{},{},{} samples,{} trimmed",
+                    h.name, h.metric, histogram.samples, histogram.trimmed
+                )?;
+                if let Some(svg) = &h.svg {
+                    write!(f, ",{}", svg)?;
+                }
+                return write!(f, "\n{}", histogram);
+            }
+
+            QueryResult::Exported(e) => {
+                return write!(f, "exported,{},{} zones", e.file, e.zones);
+            }
+
+            QueryResult::Diff(d) => {
+                write!(
+                    f,
+                    "{},{},count={:.0},mean={},median={},p95={}",
+                    d.name, d.metric, d.count, d.mean, d.median, d.p95
+                )?;
+                match d.p_value {
+                    Some(p) => write!(f, ",p={:.4}", p)?,
+                    None => write!(f, ",p=n/a")?,
+                }
+                match d.median_difference {
+                    Some(ci) => write!(f, ",median_ci=[{:.2} {:.2}]", ci.low, ci.high)?,
+                    None => write!(f, ",median_ci=n/a")?,
+                }
+                return write!(f, ",{}", d.verdict);
+            }
+        }
+    }
+}
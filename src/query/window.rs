@@ -0,0 +1,184 @@
+use serde::Deserialize;
+
+use crate::{error::TimelineError, pattern::NamePattern, zone_search::Zones};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Start,
+    End,
+}
+
+/// One end of a query's time window.
+///
+/// * a bare number is an absolute time in nanoseconds
+/// * `{"offset": 5000000000}` is nanoseconds after the start of the capture
+/// * `{"marker": "Playback", "occurrence": 2, "edge": "start"}` is the start or end of the Nth
+///   (1 based, first by default) zone matching `marker`.  `from` defaults to the marker's start and
+///   `to` to its end, so a single marker zone can delimit the whole window.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Bound {
+    Absolute(u64),
+    Offset {
+        offset: u64,
+    },
+    Marker {
+        marker: NamePattern,
+        occurrence: Option<usize>,
+        edge: Option<Edge>,
+    },
+}
+
+/// The `window` every query accepts, `{"window": {"from": ..., "to": ...}}`.  Queries only see the
+/// zones that lie entirely inside the window, so a zone is never measured with part of its
+/// children cut off.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Window {
+    pub from: Option<Bound>,
+    pub to: Option<Bound>,
+}
+
+impl Bound {
+    fn resolve(&self, zones: &Zones, default_edge: Edge) -> Result<u64, TimelineError> {
+        return match self {
+            Bound::Absolute(time) => Ok(*time),
+            Bound::Offset { offset } => {
+                Ok(zones.first().map(|z| z.start_time).unwrap_or(0).saturating_add(*offset))
+            }
+            Bound::Marker { marker, occurrence, edge } => {
+                let occurrence = occurrence.unwrap_or(1);
+                let markers = zones.matching(std::slice::from_ref(marker));
+                let zone = occurrence
+                    .checked_sub(1)
+                    .and_then(|n| markers.get(n))
+                    .map(|idx| &zones[*idx])
+                    .ok_or_else(|| TimelineError::MissingMarker {
+                        name: marker.to_string(),
+                        occurrence,
+                        found: markers.len(),
+                    })?;
+
+                Ok(match edge.unwrap_or(default_edge) {
+                    Edge::Start => zone.start_time,
+                    Edge::End => zone.end_time,
+                })
+            }
+        };
+    }
+}
+
+impl Window {
+    /// The absolute [from, to] of the window within `zones`, none when the query has no window.  A
+    /// window that starts after it ends, markers in the wrong order say, is an error rather than a
+    /// window that matches nothing.
+    pub fn resolve(&self, zones: &Zones) -> Result<Option<(u64, u64)>, TimelineError> {
+        if self.from.is_none() && self.to.is_none() {
+            return Ok(None);
+        }
+
+        let from = match &self.from {
+            Some(bound) => bound.resolve(zones, Edge::Start)?,
+            None => 0,
+        };
+        let to = match &self.to {
+            Some(bound) => bound.resolve(zones, Edge::End)?,
+            None => u64::MAX,
+        };
+
+        if from > to {
+            return Err(TimelineError::InvertedWindow { from, to });
+        }
+
+        return Ok(Some((from, to)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{query::types::Query, tests::TestZones};
+
+    fn zones() -> Zones {
+        return Zones::named(&[
+            ("startup", 100, 200, 0),
+            ("window::marker", 300, 400, 0),
+            ("work", 310, 320, 0),
+            ("window::marker", 500, 900, 0),
+            ("work", 600, 700, 0),
+            ("work", 850, 950, 0),
+        ]);
+    }
+
+    fn window(json: &str) -> Window {
+        return serde_json::from_str(json).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_bounds() -> Result<(), TimelineError> {
+        let zones = zones();
+
+        assert_eq!(window("{}").resolve(&zones)?, None);
+        assert_eq!(window(r#"{"from": 250}"#).resolve(&zones)?, Some((250, u64::MAX)));
+        assert_eq!(window(r#"{"from": {"offset": 50}, "to": {"offset": 150}}"#).resolve(&zones)?, Some((150, 250)));
+        assert_eq!(
+            window(r#"{"from": {"marker": "window::marker", "occurrence": 2}, "to": {"marker": "window::marker", "occurrence": 2}}"#)
+                .resolve(&zones)?,
+            Some((500, 900))
+        );
+        assert_eq!(
+            window(r#"{"to": {"marker": "window::marker", "edge": "start"}}"#).resolve(&zones)?,
+            Some((0, 300))
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_missing_marker() {
+        let zones = zones();
+
+        assert!(matches!(
+            window(r#"{"from": {"marker": "window::marker", "occurrence": 3}}"#).resolve(&zones),
+            Err(TimelineError::MissingMarker { found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_inverted_window() {
+        let zones = zones();
+
+        assert!(matches!(
+            window(r#"{"from": {"marker": "window::marker", "occurrence": 2}, "to": {"marker": "window::marker"}}"#)
+                .resolve(&zones),
+            Err(TimelineError::InvertedWindow { from: 500, to: 400 })
+        ));
+        assert_eq!(window(r#"{"from": 300, "to": 300}"#).resolve(&zones).unwrap(), Some((300, 300)));
+    }
+
+    #[test]
+    fn test_within() -> Result<(), TimelineError> {
+        let zones = zones();
+        let (from, to) = window(r#"{"from": {"marker": "window::marker", "occurrence": 2}, "to": {"marker": "window::marker", "occurrence": 2}}"#)
+            .resolve(&zones)?
+            .unwrap();
+
+        let within = zones.within(from, to);
+        assert_eq!(
+            within.iter().map(|z| (within.name(z), z.start_time, z.idx)).collect::<Vec<(&str, u64, usize)>>(),
+            vec![("window::marker", 500, 0), ("work", 600, 1)]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_misspelled_keys() {
+        assert!(serde_json::from_str::<Window>(r#"{"form": 250}"#).is_err());
+        assert!(serde_json::from_str::<Window>(r#"{"from": {"marker": "a", "ocurrence": 2}}"#).is_err());
+        assert!(serde_json::from_str::<Query>(r#"{"type": "Stat", "node": "a", "window": {"form": 250}}"#).is_err());
+        assert!(serde_json::from_str::<Query>(r#"{"type": "Stat", "node": "a", "from": 250}"#).is_err());
+        assert!(serde_json::from_str::<Query>(r#"{"type": "Stat", "node": "a", "window": {"from": 250}}"#).is_ok());
+    }
+}
@@ -0,0 +1,274 @@
+use serde::Serialize;
+
+pub const DEFAULT_PERCENTILES: [f64; 4] = [90.0, 95.0, 99.0, 99.9];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+/// Summary statistics of a set of samples, computed the way pandas' `describe` does.  The
+/// standard deviation is the sample standard deviation and percentiles interpolate linearly
+/// between the two closest samples.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub median: f64,
+    pub percentiles: Vec<Percentile>,
+}
+
+/// The p-th percentile (0 to 100) of already sorted samples.
+pub fn percentile(sorted: &[u64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as f64;
+
+    return sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * fraction;
+}
+
+pub fn mean(samples: &[u64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    return samples.iter().map(|s| *s as f64).sum::<f64>() / samples.len() as f64;
+}
+
+pub fn stddev(samples: &[u64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = mean(samples);
+    let variance = samples
+        .iter()
+        .map(|s| (*s as f64 - mean).powi(2))
+        .sum::<f64>()
+        / (samples.len() - 1) as f64;
+
+    return variance.sqrt();
+}
+
+impl Summary {
+    pub fn new(samples: &[u64], percentiles: &[f64]) -> Summary {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        return Summary {
+            count: sorted.len(),
+            min: sorted.first().copied().unwrap_or(0),
+            max: sorted.last().copied().unwrap_or(0),
+            mean: mean(&sorted),
+            stddev: stddev(&sorted),
+            median: percentile(&sorted, 50.0),
+            percentiles: percentiles
+                .iter()
+                .map(|p| Percentile {
+                    percentile: *p,
+                    value: percentile(&sorted, *p),
+                })
+                .collect(),
+        };
+    }
+}
+
+/// The outcome of a two sided Mann-Whitney U test of two sets of samples.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MannWhitney {
+    /// U of the first set, how many (sample of a, sample of b) pairs have a's sample above b's,
+    /// ties counting half.
+    pub u: f64,
+    pub p_value: f64,
+}
+
+/// Tests whether samples of `a` tend to be larger or smaller than samples of `b`, without
+/// assuming anything about how durations are distributed.  p comes from the normal approximation
+/// with tie and continuity correction, the same as scipy's `mannwhitneyu` does for large samples.
+/// None when either set is empty.
+pub fn mann_whitney_u(a: &[u64], b: &[u64]) -> Option<MannWhitney> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let mut all = a.iter().map(|s| (*s, true)).chain(b.iter().map(|s| (*s, false))).collect::<Vec<(u64, bool)>>();
+    all.sort_unstable_by_key(|(s, _)| *s);
+
+    // every run of equal samples shares the average of the ranks it spans.
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < all.len() {
+        let end = start + all[start..].iter().take_while(|(s, _)| *s == all[start].0).count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let in_a = all[start..end].iter().filter(|(_, from_a)| *from_a).count();
+        let tied = (end - start) as f64;
+
+        rank_sum += rank * in_a as f64;
+        ties += tied.powi(3) - tied;
+        start = end;
+    }
+
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let sigma = (n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+
+    // every sample is the same value, there is no difference to find.
+    if sigma == 0.0 || !sigma.is_finite() {
+        return Some(MannWhitney { u, p_value: 1.0 });
+    }
+
+    let z = ((u - mean).abs() - 0.5).max(0.0) / sigma;
+    let p_value = erfc(z / std::f64::consts::SQRT_2).min(1.0);
+
+    return Some(MannWhitney { u, p_value });
+}
+
+// the complementary error function, to within 1.2e-7 (Numerical Recipes' erfcc).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+        .exp();
+
+    return if x >= 0.0 { r } else { 2.0 - r };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+}
+
+// splitmix64, enough randomness for resampling and no dependency to pull in.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        return (self.next() % n as u64) as usize;
+    }
+}
+
+fn median_of(samples: &mut [u64]) -> f64 {
+    let odd = samples.len() % 2 == 1;
+    let (lower, upper, _) = samples.select_nth_unstable(samples.len() / 2);
+    let upper = *upper as f64;
+
+    if odd {
+        return upper;
+    }
+    let lower = lower.iter().max().copied().unwrap_or(0) as f64;
+    return (lower + upper) / 2.0;
+}
+
+/// A bootstrap confidence interval of `median(b) - median(a)`: both sets are resampled with
+/// replacement `resamples` times and the interval is cut from the spread of the differences.
+/// The resampling is seeded, so the same samples always give the same interval.  None when
+/// either set is empty.
+pub fn bootstrap_median_difference(a: &[u64], b: &[u64], resamples: usize, confidence: f64) -> Option<Interval> {
+    if a.is_empty() || b.is_empty() || resamples == 0 {
+        return None;
+    }
+
+    let mut rng = SplitMix64(0x5eed);
+    let mut resample_a = vec![0; a.len()];
+    let mut resample_b = vec![0; b.len()];
+    let mut differences = Vec::with_capacity(resamples);
+
+    for _ in 0..resamples {
+        resample_a.iter_mut().for_each(|s| *s = a[rng.below(a.len())]);
+        resample_b.iter_mut().for_each(|s| *s = b[rng.below(b.len())]);
+        differences.push(median_of(&mut resample_b) - median_of(&mut resample_a));
+    }
+    differences.sort_unstable_by(|x, y| x.total_cmp(y));
+
+    let tail = (1.0 - confidence.clamp(0.0, 1.0)) / 2.0 * 100.0;
+    let at = |p: f64| {
+        let rank = p / 100.0 * (differences.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        return differences[lower] + (differences[upper] - differences[lower]) * (rank - lower as f64);
+    };
+
+    return Some(Interval {
+        low: at(tail),
+        high: at(100.0 - tail),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&[5, 1, 4, 2, 3, 10, 6, 8, 7, 9], &[90.0, 99.9]);
+
+        assert_eq!(summary.count, 10);
+        assert_eq!(summary.min, 1);
+        assert_eq!(summary.max, 10);
+        assert_eq!(summary.mean, 5.5);
+        assert_eq!(summary.median, 5.5);
+        assert!((summary.stddev - 3.0276503540974917).abs() < 1e-12);
+        assert!((summary.percentiles[0].value - 9.1).abs() < 1e-12);
+        assert!((summary.percentiles[1].value - 9.991).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_summary_of_nothing() {
+        let summary = Summary::new(&[], &DEFAULT_PERCENTILES);
+
+        assert_eq!(summary.count, 0);
+        assert_eq!(summary.median, 0.0);
+        assert_eq!(summary.stddev, 0.0);
+    }
+
+    #[test]
+    fn test_mann_whitney_u() {
+        let test = mann_whitney_u(&[1, 2, 3, 4, 5], &[6, 7, 8, 9, 10]).unwrap();
+        assert_eq!(test.u, 0.0);
+        assert!((test.p_value - 0.012185).abs() < 1e-5);
+
+        let same = mann_whitney_u(&[5, 5, 5], &[5, 5]).unwrap();
+        assert_eq!(same.p_value, 1.0);
+
+        let overlapping = mann_whitney_u(&[1, 3, 5, 7, 9], &[2, 4, 6, 8, 10]).unwrap();
+        assert!(overlapping.p_value > 0.5);
+        assert_eq!(mann_whitney_u(&[], &[1]), None);
+    }
+
+    #[test]
+    fn test_bootstrap_median_difference() {
+        let a = (0..200).map(|i| 100 + i % 10).collect::<Vec<u64>>();
+        let b = (0..200).map(|i| 150 + i % 10).collect::<Vec<u64>>();
+
+        let interval = bootstrap_median_difference(&a, &b, 500, 0.95).unwrap();
+        assert!(interval.low > 40.0 && interval.high < 60.0, "{:?}", interval);
+        assert_eq!(bootstrap_median_difference(&a, &b, 500, 0.95), Some(interval));
+
+        let none = bootstrap_median_difference(&a, &a, 500, 0.95).unwrap();
+        assert!(none.low <= 0.0 && none.high >= 0.0, "{:?}", none);
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+/// An interned zone name.  Captures repeat the same handful of names millions of times, so zones
+/// only hold this id and compare names as integers.  The id means nothing without the `Symbols`
+/// it came from, the capture's table, where the name is looked up again when results are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn id(&self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+/// The table of zone names of a capture.  Every distinct name gets the next id, so ids index
+/// straight into the table.
+#[derive(Debug, Default, Clone)]
+pub struct Symbols {
+    ids: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+
+impl Symbols {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.ids.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), symbol);
+
+        return symbol;
+    }
+
+    /// The symbol of a name, without interning it.  A name that was never interned cannot belong
+    /// to any zone.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        return self.ids.get(name).copied();
+    }
+
+    /// The name of a symbol handed out by this table.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        return &self.names[symbol.id()];
+    }
+
+    /// Every symbol along with its name, in id order.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        return self.names.iter().enumerate().map(|(id, name)| (Symbol(id as u32), name.as_str()));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.names.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.names.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut symbols = Symbols::default();
+        let a = symbols.intern("foo");
+        let b = symbols.intern(&format!("f{}", "oo"));
+        let c = symbols.intern("bar");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(symbols.name(a), "foo");
+        assert_eq!(symbols.get("bar"), Some(c));
+        assert_eq!(symbols.get("never_interned"), None);
+        assert_eq!(symbols.iter().collect::<Vec<(Symbol, &str)>>(), vec![(a, "foo"), (c, "bar")]);
+    }
+}
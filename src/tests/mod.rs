@@ -1,30 +1,61 @@
-use crate::zones::Zone;
+use std::io::Cursor;
+
+use crate::{parse::CsvRows, symbols::Symbols, zone_search::Zones, zones::Zone};
 
 pub trait TestZone {
     fn from_timestamps(start_time: u64, end_time: u64) -> Zone;
-    fn from_csv_strings<'a>(strings: Vec<&'a str>) -> Result<Vec<Zone>, std::num::ParseIntError>;
 }
 
 impl TestZone for Zone {
+    // for tests where the name doesn't matter, the zone is named by a table of its own.
     fn from_timestamps(start_time: u64, end_time: u64) -> Zone {
-        return Zone::new("foo".to_string(), start_time, end_time, 0);
+        return Zone::new(Symbols::default().intern("foo"), start_time, end_time, 0);
     }
+}
 
-    // This is for when I do a Reduce query to create a test
-    // TM_ZONE,65536,V8.Builtin_HandleApiCall,1642630333023839586,1642630333023880460,6,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0
-    fn from_csv_strings(strings: Vec<&str>) -> Result<Vec<Zone>, std::num::ParseIntError> {
-        let mut out = vec![];
+pub trait TestZones {
+    fn named(zones: &[(&str, u64, u64, usize)]) -> Zones;
+    fn with_csv_rows(zones: &[(&str, u64, u64, usize)]) -> (Zones, CsvRows<Cursor<Vec<u8>>>);
+}
 
-        for string in strings {
-            let record = string.split(",").collect::<Vec<&str>>();
-            let track_id = record.get(1).unwrap().parse::<usize>()?;
-            let name = record.get(2).unwrap();
-            let start_time = record.get(3).unwrap().parse::<u64>()?;
-            let end_time = record.get(4).unwrap().parse::<u64>()?;
+impl TestZones for Zones {
+    // Every zone is `(name, start time, end time, track id)`.
+    fn named(zones: &[(&str, u64, u64, usize)]) -> Zones {
+        return Zones::with_csv_rows(zones).0;
+    }
+
+    // Writes every zone out as a TM_ZONE row and points the zone at it, for queries that print
+    // the original rows.
+    fn with_csv_rows(zones: &[(&str, u64, u64, usize)]) -> (Zones, CsvRows<Cursor<Vec<u8>>>) {
+        let mut symbols = Symbols::default();
+        let mut csv = String::new();
+        let mut out = vec![];
 
-            out.push(Zone::new(name.to_string(), start_time, end_time, track_id));
+        for (name, start_time, end_time, track_id) in zones {
+            let mut zone = Zone::new(symbols.intern(name), *start_time, *end_time, *track_id);
+            zone.csv_offset = csv.len() as u64;
+            csv.push_str(&format!("TM_ZONE,{},{},{},{}\n", track_id, name, start_time, end_time));
+            out.push(zone);
         }
 
-        return Ok(out);
+        return (Zones::new(out, symbols), CsvRows::new(Cursor::new(csv.into_bytes())));
     }
 }
+
+// This is for when I do a Reduce query to create a test
+// TM_ZONE,65536,V8.Builtin_HandleApiCall,1642630333023839586,1642630333023880460,6,0x0,0x0,/home/mpaulson/.pvm/installed/odroid-x86_64/32-release-21.2/dev/.pvm/build/src/src/base/Telemetry.h,111,0,0
+pub fn from_csv_strings<'a>(strings: &[&'a str]) -> Result<Vec<(&'a str, u64, u64, usize)>, std::num::ParseIntError> {
+    let mut out = vec![];
+
+    for string in strings {
+        let record = string.split(',').collect::<Vec<&str>>();
+        let track_id = record.get(1).unwrap().parse::<usize>()?;
+        let name = record.get(2).unwrap();
+        let start_time = record.get(3).unwrap().parse::<u64>()?;
+        let end_time = record.get(4).unwrap().parse::<u64>()?;
+
+        out.push((*name, start_time, end_time, track_id));
+    }
+
+    return Ok(out);
+}
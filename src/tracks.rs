@@ -1,18 +1,175 @@
 use csv::StringRecord;
+use log::info;
 
+use crate::{error::{RowError, TimelineError}, parse::{field, number_field}};
 
+#[derive(Debug, Clone)]
 pub struct Track {
     pub name: String,
     pub id: usize,
 }
 
+/// A TM_TRACK row: `TM_TRACK,id,name,...`.
 impl TryInto<Track> for StringRecord {
-    type Error = std::num::ParseIntError;
+    type Error = RowError;
     fn try_into(self) -> Result<Track, Self::Error> {
         return Ok(Track {
-            name: self[2].to_string(),
-            id: self[1].parse()?,
+            name: field(&self, 2, "name")?.to_string(),
+            id: number_field(&self, 1, "track id")?,
         });
     }
 }
 
+/// Simple shell style globbing.  `*` matches any run of characters and `?` matches exactly one.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+
+    let mut p = 0;
+    let mut n = 0;
+    let mut last_star: Option<usize> = None;
+    let mut star_match = 0;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some(p);
+            star_match = n;
+            p += 1;
+        } else if let Some(star) = last_star {
+            p = star + 1;
+            star_match += 1;
+            n = star_match;
+        } else {
+            return false;
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+
+    return p == pattern.len();
+}
+
+/// A track name or glob to keep.  Tracks the user named are required, the default tracks are
+/// only kept when the capture has them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackSelection {
+    pub pattern: String,
+    pub required: bool,
+}
+
+impl TrackSelection {
+    pub fn named(pattern: &str) -> TrackSelection {
+        return TrackSelection { pattern: pattern.to_string(), required: true };
+    }
+
+    pub fn default_track(pattern: &str) -> TrackSelection {
+        return TrackSelection { pattern: pattern.to_string(), required: false };
+    }
+}
+
+/// Resolves every selection to the tracks it names.  A required selection that matches no track
+/// at all is an error, typos in a track name should not quietly produce an empty result.  When
+/// nothing is required and none of the defaults are in the capture, every track is kept.
+pub fn select_tracks(tracks: &[Track], selections: &[TrackSelection]) -> Result<Vec<Track>, TimelineError> {
+    let mut out: Vec<Track> = vec![];
+
+    for selection in selections {
+        let mut found = false;
+        for track in tracks {
+            if glob_matches(&selection.pattern, &track.name) {
+                found = true;
+                if !out.iter().any(|t| t.id == track.id) {
+                    out.push(track.clone());
+                }
+            }
+        }
+
+        if !found && selection.required {
+            return Err(TimelineError::UnknownTrack(selection.pattern.to_string()));
+        }
+        if !found {
+            info!("the capture has no {} track, skipping it", selection.pattern);
+        }
+    }
+
+    if out.is_empty() && !selections.iter().any(|selection| selection.required) {
+        info!("none of the default tracks are in the capture, keeping every track");
+        return Ok(tracks.to_vec());
+    }
+
+    return Ok(out);
+}
+
+pub fn track_name(tracks: &[Track], id: usize) -> String {
+    return tracks
+        .iter()
+        .find(|track| track.id == id)
+        .map(|track| track.name.to_string())
+        .unwrap_or_else(|| id.to_string());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tracks() -> Vec<Track> {
+        return vec![
+            Track { name: "Main Thread".to_string(), id: 1 },
+            Track { name: "Instrumentation".to_string(), id: 2 },
+            Track { name: "Worker 1".to_string(), id: 3 },
+            Track { name: "Worker 2".to_string(), id: 4 },
+        ];
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("Main Thread", "Main Thread"));
+        assert!(!glob_matches("Main", "Main Thread"));
+        assert!(glob_matches("Worker *", "Worker 12"));
+        assert!(!glob_matches("Worker ?", "Worker 12"));
+        assert!(glob_matches("*Thread", "Main Thread"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn test_select_tracks() -> Result<(), TimelineError> {
+        let selected = select_tracks(&tracks(), &[
+            TrackSelection::named("Main Thread"),
+            TrackSelection::named("Worker*"),
+            TrackSelection::named("Worker 1"),
+        ])?;
+
+        assert_eq!(selected.iter().map(|t| t.id).collect::<Vec<usize>>(), vec![1, 3, 4]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_select_missing_track() {
+        let selected = select_tracks(&tracks(), &[TrackSelection::named("Render Thread")]);
+
+        match selected {
+            Err(TimelineError::UnknownTrack(name)) => assert_eq!(name, "Render Thread"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_select_default_tracks() -> Result<(), TimelineError> {
+        let defaults = [TrackSelection::default_track("Main Thread"), TrackSelection::default_track("Render Thread")];
+        assert_eq!(select_tracks(&tracks(), &defaults)?.iter().map(|t| t.id).collect::<Vec<usize>>(), vec![1]);
+
+        let missing = [TrackSelection::default_track("Render Thread")];
+        assert_eq!(select_tracks(&tracks(), &missing)?.len(), 4);
+
+        let named = [TrackSelection::default_track("Render Thread"), TrackSelection::named("Worker 2")];
+        assert_eq!(select_tracks(&tracks(), &named)?.iter().map(|t| t.id).collect::<Vec<usize>>(), vec![4]);
+
+        return Ok(());
+    }
+}
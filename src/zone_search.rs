@@ -1,176 +1,156 @@
-use crate::zones::Zone;
+use std::{cmp::Reverse, ops::Deref};
 
-pub struct ZoneIdx(usize);
+use crate::{
+    call_tree::CallTree, interval_tree::IntervalTree, pattern::NamePattern, symbols::{Symbol, Symbols},
+    zones::Zone,
+};
 
-impl Into<ZoneIdx> for usize {
-    fn into(self) -> ZoneIdx {
-        return ZoneIdx(self);
-    }
-}
-
-pub fn set_zone_idx(vec: &mut Vec<Zone>) {
+pub fn set_zone_idx(vec: &mut [Zone]) {
     vec.iter_mut().enumerate().for_each(|(idx, z)| {
         z.idx = idx;
     });
 }
 
-// TODO: Hashmap??
-pub fn get_by_name(vec: &Vec<Zone>, name: &str) -> Vec<usize> {
-    let mut out = vec![];
+/// The zones of a capture sorted by start time, along with the interval index used to answer
+/// containment queries, the call tree of every track and the table the zone names are interned
+/// in.  Derefs to the underlying `Vec<Zone>`.
+#[derive(Debug)]
+pub struct Zones {
+    zones: Vec<Zone>,
+    tree: IntervalTree,
+    calls: CallTree,
+    names: Symbols,
+    by_name: Vec<Vec<usize>>,
+}
 
-    for zone in vec {
-        if zone.name == name {
-            out.push(zone.idx);
-        }
-    }
+impl Zones {
+    /// Every zone has to be named by a symbol of `names`.
+    pub fn new(mut zones: Vec<Zone>, names: Symbols) -> Zones {
+        // longest first on ties so a parent always comes before its children
+        zones.sort_by_key(|z| (z.start_time, Reverse(z.end_time)));
+        set_zone_idx(&mut zones);
 
-    return out;
-}
+        let tree = IntervalTree::new(
+            &zones
+                .iter()
+                .map(|z| (z.start_time, z.end_time))
+                .collect::<Vec<(u64, u64)>>(),
+        );
 
-pub enum FilterResult {
-    Continue,
-    Add,
-    Break,
-}
+        let calls = CallTree::new(&zones);
 
-fn partial_intersect_filter(zone: &Zone, z: &Zone) -> FilterResult {
+        let mut by_name = vec![vec![]; names.len()];
+        for zone in &zones {
+            by_name[zone.name.id()].push(zone.idx);
+        }
 
-    if zone.partial_contains(z) {
-        return FilterResult::Add;
-    } else if zone.contains(z) || z.contains(zone) {
-        return FilterResult::Continue;
+        return Zones { zones, tree, calls, names, by_name };
+    }
 
-    // The issue is that partial contains search can be stopped by simple different track item.
-    } else if zone.track_id != z.track_id {
-        return FilterResult::Continue;
+    /// The table the zone names are interned in.
+    pub fn names(&self) -> &Symbols {
+        return &self.names;
     }
-    return FilterResult::Break;
-}
 
-fn get_parent_filter(zone: &Zone, z: &Zone) -> FilterResult {
-    if z.contains(zone) {
-        return FilterResult::Add;
-    // parents must be on the same track.
-    } else if zone.track_id != z.track_id {
-        return FilterResult::Continue;
-    // This i don't really know what to do.  There is a problem with toImplArgs causing me to lose
-    // my tree.  So I feel like I need to "allow" for a certain range of search.
-    } else if zone.start_time.abs_diff(z.start_time) < 100000 { // less than 100 micro seconds
-        return FilterResult::Continue;
+    /// The name of a zone, only needed once results are written out.
+    pub fn name(&self, zone: &Zone) -> &str {
+        return self.names.name(zone.name);
     }
 
-    return FilterResult::Break;
-}
+    /// The indices of every zone with the given name, in start order.
+    pub fn by_name(&self, name: &str) -> &[usize] {
+        return self.names.get(name).map(|symbol| self.by_symbol(symbol)).unwrap_or(&[]);
+    }
 
-fn contain_on_track_only_filter(zone: &Zone, z: &Zone) -> FilterResult {
-    if zone.contains(z) {
-        return FilterResult::Add;
-    } else if zone.track_id != z.track_id {
-        return FilterResult::Continue;
+    pub fn by_symbol(&self, symbol: Symbol) -> &[usize] {
+        return &self.by_name[symbol.id()];
     }
 
-    return FilterResult::Break;
-}
+    /// Every name of a zone in the capture matched by one of the patterns, sorted so membership
+    /// can be checked with a binary search.
+    pub fn symbols(&self, patterns: &[NamePattern]) -> Vec<Symbol> {
+        let mut out = vec![];
+        for pattern in patterns {
+            match pattern {
+                NamePattern::Exact(name) => out.extend(self.names.get(name.as_str())),
+                _ => out.extend(self.names.iter().filter(|(_, name)| pattern.matches(name)).map(|(s, _)| s)),
+            }
+        }
+        out.retain(|symbol| !self.by_symbol(*symbol).is_empty());
+        out.sort();
+        out.dedup();
 
-fn contain_intersect_filter(zone: &Zone, z: &Zone) -> FilterResult {
-    if zone.contains(z) {
-        return FilterResult::Add;
+        return out;
     }
 
-    return FilterResult::Break;
-}
+    /// The indices of every zone matched by one of the patterns, in start order.
+    pub fn matching(&self, patterns: &[NamePattern]) -> Vec<usize> {
+        let mut out = self
+            .symbols(patterns)
+            .into_iter()
+            .flat_map(|symbol| self.by_symbol(symbol).iter().copied())
+            .collect::<Vec<usize>>();
+        out.sort();
 
-fn intersect_by_filter(
-    zones: &Vec<Zone>,
-    idx: usize,
-    filter: Box<dyn Fn(&Zone, &Zone) -> FilterResult>,
-) -> Vec<usize> {
-    let mut out = vec![];
-    let zone = zones
-        .get(idx)
-        .expect("should never hand a zone that doesn't exist");
-    let mut curr_idx = idx;
-
-    loop {
-        let next_idx = curr_idx.saturating_sub(1);
-        if next_idx == curr_idx {
-            break;
-        }
+        return out;
+    }
 
-        if let Some(z) = zones.get(next_idx) {
-            match filter(zone, z) {
-                FilterResult::Add => {
-                    out.push(z.idx);
-                }
-                FilterResult::Break => {
-                    break;
-                }
-                _ => {}
-            }
-        }
+    pub fn call_tree(&self) -> &CallTree {
+        return &self.calls;
+    }
 
-        curr_idx = next_idx;
+    /// A new capture made of only the zones that lie entirely within [from, to].
+    pub fn within(&self, from: u64, to: u64) -> Zones {
+        let mut idxs = self.tree.contained(from, to);
+        idxs.sort();
+
+        return Zones::new(idxs.iter().map(|idx| self.zones[*idx].clone()).collect(), self.names.clone());
     }
 
-    let mut curr_idx = idx;
-    loop {
-        let next_idx = curr_idx + 1;
-        if next_idx >= zones.len() {
-            break;
-        }
+    pub fn into_inner(self) -> Vec<Zone> {
+        return self.zones;
+    }
+}
 
-        if let Some(z) = zones.get(next_idx) {
-            match filter(zone, z) {
-                FilterResult::Add => {
-                    out.push(z.idx);
-                }
-                FilterResult::Break => {
-                    break;
-                }
-                _ => {}
-            }
-        }
+impl Deref for Zones {
+    type Target = Vec<Zone>;
 
-        curr_idx = next_idx;
+    fn deref(&self) -> &Self::Target {
+        return &self.zones;
     }
+}
 
-    return out;
+// Results are ordered nearest first, everything to the left of the zone followed by everything to
+// the right of it.
+fn order_from(idx: usize, mut found: Vec<usize>) -> Vec<usize> {
+    found.retain(|z_idx| *z_idx != idx);
+    found.sort_by_key(|z_idx| (*z_idx > idx, z_idx.abs_diff(idx)));
+    return found;
 }
 
-pub fn sum_zone_indices(zones: &Vec<Zone>, zone: &Zone, containers: &Vec<usize>) -> u64 {
-    return containers
-        .iter()
-        .map(|z_idx| {
-            let other_zone = zones
-                .get(*z_idx)
-                .expect("all indices should be valid");
-            return zone.get_duration_intersection(other_zone);
-        })
-        .sum();
+pub fn get_by_name(zones: &Zones, name: &NamePattern) -> Vec<usize> {
+    return zones.matching(std::slice::from_ref(name));
 }
 
 // TODO: I could get really clever with this algo and make it o(N), but
 // that is hard and I don't want to do it...
 pub fn filter_out_contains(
-    zones: &Vec<Zone>,
-    containers: &Vec<usize>,
-    possible_contains: &Vec<usize>,
+    zones: &[Zone],
+    containers: &[usize],
+    possible_contains: &[usize],
 ) -> Vec<usize> {
     let mut out = vec![];
 
     for possible in possible_contains {
-        let possible_zone = zones.get(*possible).expect("all indices should be valid");
-        let mut contained = false;
-
-        for container in containers {
-            contained = zones
-                .get(*container)
-                .expect("all indices should be valid")
-                .contains(&possible_zone);
-            if contained {
-                break;
-            }
-        }
+        let possible_zone = match zones.get(*possible) {
+            Some(zone) => zone,
+            None => continue,
+        };
+
+        let contained = containers
+            .iter()
+            .filter_map(|container| zones.get(*container))
+            .any(|container| container.contains(possible_zone));
 
         if !contained {
             out.push(*possible);
@@ -180,16 +160,16 @@ pub fn filter_out_contains(
     return out;
 }
 
+/// `names` has to be sorted, see `Zones::symbols`.
 pub fn filter_by_names_on_idx(
-    zones: &Vec<Zone>,
-    filter_zones: &Vec<usize>,
-    names: &Vec<String>,
+    zones: &[Zone],
+    filter_zones: &[usize],
+    names: &[Symbol],
 ) -> Vec<usize> {
     let mut out = vec![];
 
-    for zone_idx in filter_zones {
-        let zone = zones.get(*zone_idx).expect("all indices should be valid");
-        if names.contains(&zone.name) {
+    for zone in filter_zones.iter().filter_map(|idx| zones.get(*idx)) {
+        if names.binary_search(&zone.name).is_ok() {
             out.push(zone.idx);
         }
     }
@@ -197,23 +177,14 @@ pub fn filter_by_names_on_idx(
     return out;
 }
 
-pub fn filter_by_name_on_idx(zones: &Vec<Zone>, zones_to_filter: &Vec<usize>, name: &str) -> Vec<usize> {
-    let mut out = vec![];
-
-    for zone_idx in zones_to_filter {
-        let zone = zones.get(*zone_idx).unwrap();
-        if zone.name == name {
-            out.push(zone.idx);
-        }
-    }
-
-    return out;
-}
+pub fn filter_by_name_on_idx(zones: &Zones, zones_to_filter: &[usize], name: &str) -> Vec<usize> {
+    let name = match zones.names().get(name) {
+        Some(name) => name,
+        None => return vec![],
+    };
 
-pub fn filter_by_name(zones: &Vec<Zone>, name: &str) -> Vec<usize> {
     let mut out = vec![];
-
-    for zone in zones {
+    for zone in zones_to_filter.iter().filter_map(|idx| zones.get(*idx)) {
         if zone.name == name {
             out.push(zone.idx);
         }
@@ -222,91 +193,125 @@ pub fn filter_by_name(zones: &Vec<Zone>, name: &str) -> Vec<usize> {
     return out;
 }
 
-pub fn filter_by_names(zones: &Vec<Zone>, names: &Vec<String>) -> Vec<usize> {
-    let mut out = vec![];
-
-    for zone in zones {
-        if names.contains(&zone.name) {
-            out.push(zone.idx);
-        }
-    }
+pub fn filter_by_name(zones: &Zones, name: &NamePattern) -> Vec<usize> {
+    return zones.matching(std::slice::from_ref(name));
+}
 
-    return out;
+pub fn filter_by_names(zones: &Zones, names: &[NamePattern]) -> Vec<usize> {
+    return zones.matching(names);
 }
 
-pub fn get_contained_by_name(zones: &Vec<Zone>, idx: usize, name: &str) -> Vec<usize> {
-    return intersect_by_filter(zones, idx, Box::new(contain_intersect_filter))
-        .into_iter()
-        .filter(|z_idx| zones.get(*z_idx).unwrap().name == name)
-        .collect();
+pub fn get_contained_by_name(zones: &Zones, idx: usize, name: &str) -> Vec<usize> {
+    return filter_by_name_on_idx(zones, &get_contained(zones, idx), name);
 }
 
-pub fn get_parents(zones: &Vec<Zone>, idx: usize) -> Vec<usize> {
-    return intersect_by_filter(zones, idx, Box::new(get_parent_filter));
+/// Every zone on the same track that contains the zone at idx.
+pub fn get_parents(zones: &Zones, idx: usize) -> Vec<usize> {
+    let zone = &zones[idx];
+    let mut parents = zones.tree.containing(zone.start_time, zone.end_time);
+    parents.retain(|z_idx| zones[*z_idx].track_id == zone.track_id);
+
+    return order_from(idx, parents);
 }
 
-pub fn get_contained(zones: &Vec<Zone>, idx: usize) -> Vec<usize> {
-    return intersect_by_filter(zones, idx, Box::new(contain_intersect_filter));
+/// Every zone, on any track, that the zone at idx contains.
+pub fn get_contained(zones: &Zones, idx: usize) -> Vec<usize> {
+    let zone = &zones[idx];
+    return order_from(idx, zones.tree.contained(zone.start_time, zone.end_time));
 }
 
-pub fn contained_on_track(zones: &Vec<Zone>, idx: usize) -> Vec<usize> {
-    return intersect_by_filter(zones, idx, Box::new(contain_on_track_only_filter));
+pub fn contained_on_track(zones: &Zones, idx: usize) -> Vec<usize> {
+    let zone = &zones[idx];
+    let mut contained = zones.tree.contained(zone.start_time, zone.end_time);
+    contained.retain(|z_idx| zones[*z_idx].track_id == zone.track_id);
+
+    return order_from(idx, contained);
 }
 
-pub fn get_partial_contained(zones: &Vec<Zone>, idx: usize) -> Vec<usize> {
-    return intersect_by_filter(zones, idx, Box::new(partial_intersect_filter));
+/// Every zone, on any track, that crosses the start or the end of the zone at idx.
+pub fn get_partial_contained(zones: &Zones, idx: usize) -> Vec<usize> {
+    let zone = &zones[idx];
+    return order_from(idx, zones.tree.partially_overlapping(zone.start_time, zone.end_time));
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
-    use crate::tests::TestZone;
+    use crate::tests::{TestZone, TestZones};
 
     #[test]
     fn test_filter_by_name() {
-        let mut zones = vec![
-            Zone::new("foo".to_string(), 8, 20, 0),
-            Zone::new("foo2".to_string(), 10, 50, 0),
-            Zone::new("foo".to_string(), 30, 40, 0),
-            Zone::new("foo4".to_string(), 32, 56, 0),
-            Zone::new("foo".to_string(), 48, 55, 0),
-            Zone::new("foo6".to_string(), 55, 65, 0),
-        ];
-        set_zone_idx(&mut zones);
-
-        let filtered_zones = filter_by_names(&zones, &vec!["foo".to_string()]);
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo2", 10, 50, 0),
+            ("foo", 30, 40, 0),
+            ("foo4", 32, 56, 0),
+            ("foo", 48, 55, 0),
+            ("foo6", 55, 65, 0),
+        ]);
+
+        let filtered_zones = filter_by_names(&zones, &["foo".into()]);
         assert_eq!(filtered_zones.len(), 3);
-        assert_eq!(filtered_zones.get(0).unwrap(), &0);
+        assert_eq!(filtered_zones.first().unwrap(), &0);
         assert_eq!(filtered_zones.get(1).unwrap(), &2);
         assert_eq!(filtered_zones.get(2).unwrap(), &4);
     }
 
     #[test]
-    fn test_get_by_name() {
-        let mut zones = vec![
-            Zone::new("foo".to_string(), 8, 20, 0),
-            Zone::new("foo2".to_string(), 10, 50, 0),
-            Zone::new("foo".to_string(), 30, 40, 0),
-            Zone::new("foo4".to_string(), 32, 56, 0),
-            Zone::new("foo".to_string(), 48, 55, 0),
-            Zone::new("foo6".to_string(), 55, 65, 0),
-        ];
-        set_zone_idx(&mut zones);
+    fn test_filter_by_many_names() {
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo2", 10, 50, 0),
+            ("foo", 30, 40, 0),
+            ("foo4", 32, 56, 0),
+        ]);
+
+        let names = vec!["foo4".into(), "foo".into(), "foo4".into(), "missing".into()];
+        assert_eq!(filter_by_names(&zones, &names), vec![0, 2, 3]);
+        assert_eq!(zones.by_name("missing"), &[] as &[usize]);
+    }
+
+    #[test]
+    fn test_filter_by_patterns() {
+        let zones = Zones::named(&[
+            ("INST_DataBufferBridge_CLASSgetUint8", 8, 20, 0),
+            ("DataBufferBridge.getUint8", 10, 50, 0),
+            ("INST_Console_CLASSlog", 30, 40, 0),
+            ("INST_Console_CLASSwarn", 32, 56, 0),
+        ]);
+
+        let glob: NamePattern = serde_json::from_str(r#"{"glob": "INST_*_CLASS*"}"#).unwrap();
+        let regex: NamePattern = serde_json::from_str(r#"{"regex": "CLASS(get|log)"}"#).unwrap();
+
+        assert_eq!(filter_by_name(&zones, &glob), vec![0, 2, 3]);
+        assert_eq!(filter_by_name(&zones, &regex), vec![0, 2]);
+        assert_eq!(filter_by_names(&zones, &[regex, "DataBufferBridge.getUint8".into()]), vec![0, 1, 2]);
+    }
 
-        assert_eq!(get_by_name(&zones, "foo"), vec![0, 2, 4],);
+    #[test]
+    fn test_get_by_name() {
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo2", 10, 50, 0),
+            ("foo", 30, 40, 0),
+            ("foo4", 32, 56, 0),
+            ("foo", 48, 55, 0),
+            ("foo6", 55, 65, 0),
+        ]);
+
+        assert_eq!(get_by_name(&zones, &"foo".into()), vec![0, 2, 4],);
     }
     #[test]
     fn test_partial_intersection() {
-        let mut zones = vec![
-            Zone::from_timestamps(8, 20),
-            Zone::from_timestamps(10, 50),
-            Zone::from_timestamps(30, 40),
-            Zone::from_timestamps(32, 56),
-            Zone::from_timestamps(48, 55),
-            Zone::from_timestamps(55, 65),
-        ];
-        set_zone_idx(&mut zones);
+        let zones = Zones::named(&[
+            ("foo", 8, 20, 0),
+            ("foo", 10, 50, 0),
+            ("foo", 30, 40, 0),
+            ("foo", 32, 56, 0),
+            ("foo", 48, 55, 0),
+            ("foo", 55, 65, 0),
+        ]);
 
         assert_eq!(get_partial_contained(&zones, 3), vec![2, 1, 5]);
 
@@ -317,11 +322,10 @@ mod test {
 
     #[test]
     fn test_partial_intersection_with_super_container() {
-        let mut zones = vec![
-            Zone::from_timestamps(8, 55),
-            Zone::from_timestamps(10, 50),
-        ];
-        set_zone_idx(&mut zones);
+        let zones = Zones::named(&[
+            ("foo", 8, 55, 0),
+            ("foo", 10, 50, 0),
+        ]);
 
         let expected: Vec<usize> = vec![];
         assert_eq!(get_partial_contained(&zones, 1), expected);
@@ -329,12 +333,11 @@ mod test {
 
     #[test]
     fn test_partial_off_track_no_search_stop() {
-        let mut zones = vec![
-            Zone::from_timestamps(5, 25),
-            Zone::new("foo6".to_string(), 8, 9, 1), // off track zone
-            Zone::from_timestamps(10, 50),
-        ];
-        set_zone_idx(&mut zones);
+        let zones = Zones::named(&[
+            ("foo", 5, 25, 0),
+            ("foo6", 8, 9, 1), // off track zone
+            ("foo", 10, 50, 0),
+        ]);
 
         let expected: Vec<usize> = vec![0];
         assert_eq!(get_partial_contained(&zones, 2), expected);
@@ -359,6 +362,38 @@ mod test {
         let contains = vec![1, 3];
         let contains = filter_out_contains(&zones, &partials, &contains);
         assert_eq!(contains.len(), 1);
-        assert_eq!(contains.get(0).unwrap(), &3);
+        assert_eq!(contains.first().unwrap(), &3);
+    }
+
+    #[test]
+    fn test_contained_past_interleaved_zones() {
+        let zones = Zones::named(&[
+            ("foo", 10, 100, 0),
+            ("other", 12, 200, 1), // unrelated zone interleaving
+            ("foo", 20, 30, 0),
+            ("foo", 40, 50, 0),
+        ]);
+
+        assert_eq!(get_contained(&zones, 0), vec![2, 3]);
+        assert_eq!(contained_on_track(&zones, 0), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_get_parents() {
+        let zones = Zones::named(&[
+            ("foo", 0, 1_000_000, 0),
+            ("foo", 10, 20, 0), // unrelated sibling, far more than 100us away
+            ("other track", 500_000, 800_000, 1),
+            ("foo", 400_000, 900_000, 0),
+            ("foo", 600_000, 700_000, 0),
+        ]);
+
+        let child = zones.iter().find(|z| z.start_time == 600_000).unwrap().idx;
+        let parents = get_parents(&zones, child)
+            .into_iter()
+            .map(|idx| zones[idx].start_time)
+            .collect::<Vec<u64>>();
+
+        assert_eq!(parents, vec![400_000, 0]);
     }
 }
@@ -1,34 +1,46 @@
 use std::fmt::Display;
 
 use csv::StringRecord;
-use itertools::Itertools;
 
-use crate::error::TimelineError;
+use crate::{error::RowError, parse::{field, number_field}, symbols::{Symbol, Symbols}};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Zone {
-    pub name: String,
+    pub name: Symbol,
     pub start_time: u64,
     pub end_time: u64,
     pub duration: u64,
     pub idx: usize,
     pub track_id: usize,
-    pub original_csv: String,
+
+    /// Byte offset of the row this zone was parsed from, see `parse::RowSource`.
+    pub csv_offset: u64,
 }
 
 impl Zone {
-    pub fn from_record(record: &StringRecord) -> Result<Self, TimelineError> {
-        let mut zone = Zone::new(
-            record[2].to_string(),
-            record[3].parse()?,
-            record[4].parse()?,
-            record[1].parse()?,
-        );
-        zone.original_csv = record.iter().join(",");
+    /// A TM_ZONE row: `TM_ZONE,track id,name,start,end,...`.  The name is interned in `symbols`.
+    pub fn from_record(record: &StringRecord, symbols: &mut Symbols) -> Result<Self, RowError> {
+        let track_id = number_field(record, 1, "track id")?;
+        let name = field(record, 2, "name")?;
+        let start_time: u64 = number_field(record, 3, "start time")?;
+        let end_time: u64 = number_field(record, 4, "end time")?;
+
+        if end_time < start_time {
+            return Err(RowError {
+                field: "end time",
+                reason: format!("{} is before the start time {}", end_time, start_time),
+            });
+        }
+
+        let mut zone = Zone::new(symbols.intern(name), start_time, end_time, track_id);
+        if let Some(position) = record.position() {
+            zone.csv_offset = position.byte();
+        }
         return Ok(zone);
     }
 
-    pub fn new(name: String, start_time: u64, end_time: u64, track_id: usize) -> Zone {
+    /// A zone named by a symbol of the table the capture is built with, see `Zones::new`.
+    pub fn new(name: Symbol, start_time: u64, end_time: u64, track_id: usize) -> Zone {
         return Zone {
             name,
             start_time,
@@ -36,7 +48,7 @@ impl Zone {
             duration: start_time.abs_diff(end_time),
             idx: 0,
             track_id,
-            original_csv: "".to_string(),
+            csv_offset: 0,
         };
     }
 
@@ -73,19 +85,11 @@ impl Zone {
     }
 }
 
-impl TryInto<Zone> for StringRecord {
-    type Error = TimelineError;
-
-    fn try_into(self) -> Result<Zone, Self::Error> {
-        return Ok(Zone::from_record(&self)?);
-    }
-}
-
 impl Display for Zone {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         return write!(
             f,
-            "{}: Zone({}): {},{},{}",
+            "{}: Zone({}): {:?},{},{}",
             self.track_id, self.duration, self.name, self.start_time, self.end_time
         );
     }
@@ -118,9 +122,9 @@ mod test {
         let d = Zone::from_timestamps(30, 40);
         let e = Zone::from_timestamps(55, 65);
 
-        assert_eq!(a.contains(&b), false);
-        assert_eq!(a.contains(&c), false);
-        assert_eq!(a.contains(&d), true);
-        assert_eq!(a.contains(&e), false);
+        assert!(!a.contains(&b));
+        assert!(!a.contains(&c));
+        assert!(a.contains(&d));
+        assert!(!a.contains(&e));
     }
 }